@@ -0,0 +1,460 @@
+use std::collections::HashMap;
+
+use chrono::{Datelike, NaiveDate};
+
+use crate::{
+  merchant::{self, MerchantNormalizer},
+  pc_types::{self, CategoryType, Interval},
+};
+
+// The first day of the period that `date` falls in. Weeks start on Monday.
+pub fn period_start(date: NaiveDate, interval: Interval) -> NaiveDate {
+  match interval {
+    Interval::Day => date,
+    Interval::Week => date - chrono::Duration::days(date.weekday().num_days_from_monday() as i64),
+    Interval::Month => NaiveDate::from_ymd(date.year(), date.month(), 1),
+    Interval::Year => NaiveDate::from_ymd(date.year(), 1, 1),
+  }
+}
+
+// The last day of the period that `date` falls in.
+pub fn period_end(date: NaiveDate, interval: Interval) -> NaiveDate {
+  let start = period_start(date, interval);
+  let next = match interval {
+    Interval::Day => start + chrono::Duration::days(1),
+    Interval::Week => start + chrono::Duration::days(7),
+    Interval::Month if start.month() == 12 => NaiveDate::from_ymd(start.year() + 1, 1, 1),
+    Interval::Month => NaiveDate::from_ymd(start.year(), start.month() + 1, 1),
+    Interval::Year => NaiveDate::from_ymd(start.year() + 1, 1, 1),
+  };
+
+  next - chrono::Duration::days(1)
+}
+
+// Where money in a transaction (or split) went.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Flow {
+  Spending,
+  Income,
+  Transfer,
+  // Investment activity and anything else that is neither.
+  Other,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GroupBy {
+  Category,
+  // A transaction with several tags counts towards each of them.
+  Tag,
+  Merchant,
+  Account,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum RollupKey {
+  Category(i64),
+  Tag(i64),
+  Untagged,
+  Merchant(String),
+  Account(i64),
+}
+
+// One part of a transaction: the whole transaction, or one of its splits.
+// Spending amounts are positive for purchases and negative for refunds, income
+// amounts positive for money received.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Allocation<'t> {
+  pub transaction: &'t pc_types::Transaction,
+  pub category_id: i64,
+  pub tag_ids: Vec<i64>,
+  pub flow: Flow,
+  pub amount: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RollupEntry {
+  pub period: NaiveDate,
+  pub key: RollupKey,
+  pub name: String,
+  pub amount: f64,
+  pub count: usize,
+}
+
+// Rolls transactions up by category, tag, merchant or account using the
+// user's categories and tags for names and transfer detection. Duplicates are
+// left out unless include_duplicates() is set.
+#[derive(Debug, Clone)]
+pub struct Analytics<'a> {
+  categories: HashMap<i64, &'a pc_types::Category>,
+  tags: HashMap<i64, &'a pc_types::Tag>,
+  include_duplicates: bool,
+  normalizer: MerchantNormalizer,
+}
+
+impl<'a> Analytics<'a> {
+  pub fn new(categories: &'a pc_types::Categories, tags: &'a pc_types::Tags) -> Self {
+    Analytics {
+      categories: categories
+        .iter()
+        .map(|c| (c.transaction_category_id, c))
+        .collect(),
+      tags: tags.iter().map(|t| (t.tag_id, t)).collect(),
+      include_duplicates: false,
+      normalizer: MerchantNormalizer::default(),
+    }
+  }
+
+  pub fn include_duplicates(&mut self, value: bool) -> &mut Self {
+    self.include_duplicates = value;
+    self
+  }
+
+  // Keys transactions for GroupBy::Merchant.
+  pub fn merchant_normalizer(&mut self, value: MerchantNormalizer) -> &mut Self {
+    self.normalizer = value;
+    self
+  }
+
+  fn category_type(&self, category_id: i64) -> Option<CategoryType> {
+    self.categories.get(&category_id).map(|c| c.category_type)
+  }
+
+  pub fn category_name(&self, category_id: i64) -> Option<&str> {
+    self.categories.get(&category_id).map(|c| c.name.as_str())
+  }
+
+  pub fn tag_name(&self, tag_id: i64) -> Option<&str> {
+    self.tags.get(&tag_id).map(|t| t.tag_name.as_str())
+  }
+
+  fn transaction_flow(&self, t: &pc_types::Transaction) -> Flow {
+    if t.transaction_type == pc_types::TransactionType::Transfer
+      || self.category_type(t.category_id) == Some(CategoryType::Transfer)
+    {
+      Flow::Transfer
+    } else if t.is_spending {
+      Flow::Spending
+    } else if t.is_income {
+      Flow::Income
+    } else {
+      Flow::Other
+    }
+  }
+
+  // The server only flags the parent transaction, so a split's flow comes from
+  // its own category, falling back to the parent's for uncategorized parts.
+  fn split_flow(&self, t: &pc_types::Transaction, split: &pc_types::Split) -> Flow {
+    match self.category_type(split.category_id) {
+      Some(CategoryType::Expense) => Flow::Spending,
+      Some(CategoryType::Income) => Flow::Income,
+      Some(CategoryType::Transfer) => Flow::Transfer,
+      _ => self.transaction_flow(t),
+    }
+  }
+
+  // Breaks a transaction into allocations, one per split if it has any.
+  // Returns nothing for duplicates unless include_duplicates() is set.
+  pub fn allocations<'t>(&self, t: &'t pc_types::Transaction) -> Vec<Allocation<'t>> {
+    if t.is_duplicate && !self.include_duplicates {
+      return vec![];
+    }
+
+    let tag_ids = |tags: Option<&pc_types::CustomTags>| -> Vec<i64> {
+      tags
+        .map(|c| c.user_tags.iter().chain(&c.system_tags).copied().collect())
+        .unwrap_or_default()
+    };
+
+    let signed = |flow: Flow, amount: f64| -> f64 {
+      match flow {
+        Flow::Spending if t.is_credit => -amount,
+        Flow::Spending => amount,
+        _ if t.is_credit => amount,
+        _ => -amount,
+      }
+    };
+
+    match t.splits.as_deref() {
+      Some(splits) if !splits.is_empty() => splits
+        .iter()
+        .map(|s| {
+          let flow = self.split_flow(t, s);
+          Allocation {
+            transaction: t,
+            category_id: s.category_id,
+            tag_ids: tag_ids(s.custom_tags.as_ref().or(t.custom_tags.as_ref())),
+            flow,
+            amount: signed(flow, s.amount),
+          }
+        })
+        .collect(),
+      _ => {
+        let flow = self.transaction_flow(t);
+        vec![Allocation {
+          transaction: t,
+          category_id: t.category_id,
+          tag_ids: tag_ids(t.custom_tags.as_ref()),
+          flow,
+          amount: signed(flow, t.amount),
+        }]
+      },
+    }
+  }
+
+  // Totals the allocations with the given flow per period and group. Entries
+  // are ordered by period, then by amount with the largest first.
+  pub fn rollup(
+    &self,
+    transactions: &[pc_types::Transaction],
+    group_by: GroupBy,
+    interval: Interval,
+    flow: Flow,
+  ) -> Vec<RollupEntry> {
+    let mut totals: HashMap<(NaiveDate, RollupKey), RollupEntry> = HashMap::new();
+
+    for a in transactions
+      .iter()
+      .flat_map(|t| self.allocations(t))
+      .filter(|a| a.flow == flow)
+    {
+      let period = period_start(a.transaction.transaction_date, interval);
+      for (key, name) in self.keys(&a, group_by) {
+        let entry = totals
+          .entry((period, key.clone()))
+          .or_insert_with(|| RollupEntry {
+            period,
+            key,
+            name,
+            amount: 0.0,
+            count: 0,
+          });
+        entry.amount += a.amount;
+        entry.count += 1;
+      }
+    }
+
+    let mut entries: Vec<_> = totals.into_values().collect();
+    entries.sort_by(|a, b| {
+      a.period
+        .cmp(&b.period)
+        .then_with(|| {
+          b.amount
+            .partial_cmp(&a.amount)
+            .unwrap_or(std::cmp::Ordering::Equal)
+        })
+        .then_with(|| a.key.cmp(&b.key))
+    });
+    entries
+  }
+
+  fn keys(&self, a: &Allocation, group_by: GroupBy) -> Vec<(RollupKey, String)> {
+    let t = a.transaction;
+    match group_by {
+      GroupBy::Category => vec![(
+        RollupKey::Category(a.category_id),
+        self
+          .category_name(a.category_id)
+          .unwrap_or_default()
+          .to_string(),
+      )],
+      GroupBy::Tag if a.tag_ids.is_empty() => vec![(RollupKey::Untagged, String::new())],
+      GroupBy::Tag => a
+        .tag_ids
+        .iter()
+        .map(|id| {
+          (
+            RollupKey::Tag(*id),
+            self.tag_name(*id).unwrap_or_default().to_string(),
+          )
+        })
+        .collect(),
+      GroupBy::Merchant => vec![(
+        RollupKey::Merchant(self.normalizer.key(t)),
+        merchant::merchant_name(t).to_string(),
+      )],
+      GroupBy::Account => vec![(
+        RollupKey::Account(t.user_account_id),
+        t.account_name.clone(),
+      )],
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use pretty_assertions::assert_eq;
+  use serde_json::json;
+
+  use super::*;
+  use crate::test_util::transaction;
+
+  fn category(id: i64, name: &str, category_type: &str) -> pc_types::Category {
+    serde_json::from_value(json!({
+      "isEditable": false,
+      "name": name,
+      "isCustom": false,
+      "isOverride": false,
+      "transactionCategoryId": id,
+      "type": category_type,
+    }))
+    .unwrap()
+  }
+
+  #[test]
+  fn test_period_start() {
+    let d = NaiveDate::from_ymd(2021, 3, 18);
+    assert_eq!(period_start(d, Interval::Day), d);
+    assert_eq!(
+      period_start(d, Interval::Week),
+      NaiveDate::from_ymd(2021, 3, 15)
+    );
+    assert_eq!(
+      period_start(d, Interval::Month),
+      NaiveDate::from_ymd(2021, 3, 1)
+    );
+    assert_eq!(
+      period_start(d, Interval::Year),
+      NaiveDate::from_ymd(2021, 1, 1)
+    );
+    assert_eq!(
+      period_end(NaiveDate::from_ymd(2021, 12, 5), Interval::Month),
+      NaiveDate::from_ymd(2021, 12, 31)
+    );
+  }
+
+  #[test]
+  fn test_rollup() {
+    let categories = vec![
+      category(1, "Groceries", "EXPENSE"),
+      category(2, "Restaurants", "EXPENSE"),
+      category(3, "Transfers", "TRANSFER"),
+      category(4, "Paychecks", "INCOME"),
+    ];
+    let tags = vec![];
+    let analytics = Analytics::new(&categories, &tags);
+
+    let transactions = vec![
+      transaction(json!({"amount": 40.0})),
+      transaction(json!({"amount": 15.0, "categoryId": 2, "transactionDate": "2021-01-20"})),
+      // Refund.
+      transaction(json!({"amount": 5.0, "isCredit": true, "transactionDate": "2021-01-03"})),
+      transaction(json!({"amount": 40.0, "isDuplicate": true})),
+      transaction(json!({"amount": 500.0, "categoryId": 3, "isSpending": false})),
+      transaction(json!({
+        "amount": 100.0,
+        "transactionDate": "2021-02-01",
+        "splits": [
+          {"amount": 60.0, "userTransactionId": "1", "categoryId": 1},
+          {"amount": 30.0, "userTransactionId": "2", "categoryId": 2},
+          {"amount": 10.0, "userTransactionId": "3", "categoryId": 3},
+        ],
+      })),
+    ];
+
+    let rollup: Vec<_> = analytics
+      .rollup(
+        &transactions,
+        GroupBy::Category,
+        Interval::Month,
+        Flow::Spending,
+      )
+      .into_iter()
+      .map(|e| (e.period.month(), e.name, e.amount, e.count))
+      .collect();
+    assert_eq!(
+      rollup,
+      vec![
+        (1, "Groceries".to_string(), 35.0, 2),
+        (1, "Restaurants".to_string(), 15.0, 1),
+        (2, "Groceries".to_string(), 60.0, 1),
+        (2, "Restaurants".to_string(), 30.0, 1),
+      ]
+    );
+
+    let transfers = analytics.rollup(
+      &transactions,
+      GroupBy::Account,
+      Interval::Year,
+      Flow::Transfer,
+    );
+    assert_eq!(transfers.len(), 1);
+    assert_eq!(transfers[0].amount, -510.0);
+  }
+
+  #[test]
+  fn test_rollup_groups() {
+    let categories = vec![
+      category(1, "Groceries", "EXPENSE"),
+      category(4, "Paychecks", "INCOME"),
+    ];
+    let tag = |id: i64, name: &str| pc_types::Tag {
+      tag_id: id,
+      tag_name: name.to_string(),
+    };
+    let tags = vec![tag(7, "Vacation"), tag(8, "Work")];
+    let analytics = Analytics::new(&categories, &tags);
+
+    let paycheck = |date: &str| {
+      transaction(json!({
+        "amount": 1000.0,
+        "description": "ACME PAYROLL",
+        "categoryId": 4,
+        "transactionDate": date,
+        "isCredit": true,
+        "isSpending": false,
+        "isIncome": true,
+      }))
+    };
+    let transactions = vec![
+      transaction(json!({
+        "amount": 20.0,
+        "description": "SQ *COFFEE SHOP 1234 SEATTLE WA",
+        "customTags": {"systemTags": [], "userTags": [7]},
+      })),
+      transaction(json!({
+        "amount": 30.0,
+        "merchant": "Coffee Shop",
+        "description": "Espresso beans",
+        "customTags": {"systemTags": [], "userTags": [7, 8]},
+      })),
+      transaction(json!({"amount": 50.0, "description": "Trader Joe's #552"})),
+      paycheck("2021-01-15"),
+      paycheck("2021-02-15"),
+    ];
+
+    let rollup = |group_by: GroupBy, interval: Interval, flow: Flow| -> Vec<_> {
+      analytics
+        .rollup(&transactions, group_by, interval, flow)
+        .into_iter()
+        .map(|e| (e.key, e.name, e.amount, e.count))
+        .collect()
+    };
+
+    // A transaction with several tags counts towards each of them.
+    assert_eq!(
+      rollup(GroupBy::Tag, Interval::Month, Flow::Spending),
+      vec![
+        (RollupKey::Tag(7), "Vacation".to_string(), 50.0, 2),
+        (RollupKey::Untagged, String::new(), 50.0, 1),
+        (RollupKey::Tag(8), "Work".to_string(), 30.0, 1),
+      ]
+    );
+
+    let merchants: Vec<_> = rollup(GroupBy::Merchant, Interval::Month, Flow::Spending)
+      .into_iter()
+      .map(|(key, _, amount, count)| (key, amount, count))
+      .collect();
+    assert_eq!(
+      merchants,
+      vec![
+        (RollupKey::Merchant("COFFEE SHOP".to_string()), 50.0, 2),
+        (RollupKey::Merchant("TRADER JOES".to_string()), 50.0, 1),
+      ]
+    );
+
+    assert_eq!(
+      rollup(GroupBy::Category, Interval::Year, Flow::Income),
+      vec![(RollupKey::Category(4), "Paychecks".to_string(), 2000.0, 2)]
+    );
+  }
+}
@@ -0,0 +1,211 @@
+use chrono::NaiveDate;
+
+use crate::{
+  analytics::{self, Analytics, Flow},
+  pc_types::{self, Interval},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BudgetTarget {
+  Category(i64),
+  Tag(i64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BudgetPeriod {
+  // The calendar month or year containing the evaluation date.
+  Monthly,
+  Annual,
+  // The given number of days up to and including the evaluation date.
+  Rolling(u32),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Budget {
+  pub name: String,
+  pub target: BudgetTarget,
+  pub period: BudgetPeriod,
+  pub amount: f64,
+}
+
+impl Budget {
+  pub fn new(name: &str, target: BudgetTarget, period: BudgetPeriod, amount: f64) -> Self {
+    Budget {
+      name: name.to_string(),
+      target,
+      period,
+      amount,
+    }
+  }
+
+  // The inclusive date range this budget covers on `as_of`.
+  pub fn range(&self, as_of: NaiveDate) -> (NaiveDate, NaiveDate) {
+    match self.period {
+      BudgetPeriod::Monthly => (
+        analytics::period_start(as_of, Interval::Month),
+        analytics::period_end(as_of, Interval::Month),
+      ),
+      BudgetPeriod::Annual => (
+        analytics::period_start(as_of, Interval::Year),
+        analytics::period_end(as_of, Interval::Year),
+      ),
+      BudgetPeriod::Rolling(days) => (
+        as_of - chrono::Duration::days(i64::from(days.max(1)) - 1),
+        as_of,
+      ),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BudgetStatus {
+  pub budget: Budget,
+  pub start: NaiveDate,
+  pub end: NaiveDate,
+  // Net spending so far; refunds count against it.
+  pub spent: f64,
+  // What is left before the budget is exceeded, never negative.
+  pub remaining: f64,
+  // How far spending is over the budget, never negative.
+  pub overspent: f64,
+  // Spending at the end of the period if it carries on at the current daily
+  // rate. Rolling budgets are always complete, so this equals `spent`.
+  pub projected: f64,
+}
+
+impl BudgetStatus {
+  pub fn is_over(&self) -> bool {
+    self.overspent > 0.0
+  }
+
+  pub fn is_on_pace(&self) -> bool {
+    self.projected <= self.budget.amount
+  }
+}
+
+// A set of per-category or per-tag budgets evaluated against fetched
+// transactions. Only spending counts; transfers, income and duplicates are
+// left out the same way Analytics does.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Budgets {
+  budgets: Vec<Budget>,
+}
+
+impl Budgets {
+  pub fn new() -> Self {
+    Budgets::default()
+  }
+
+  pub fn budget(&mut self, budget: Budget) -> &mut Self {
+    self.budgets.push(budget);
+    self
+  }
+
+  pub fn budgets(&self) -> &[Budget] {
+    &self.budgets
+  }
+
+  // Evaluates every budget for the period containing `as_of`. Transactions
+  // after `as_of` are ignored.
+  pub fn evaluate(
+    &self,
+    analytics: &Analytics,
+    transactions: &[pc_types::Transaction],
+    as_of: NaiveDate,
+  ) -> Vec<BudgetStatus> {
+    let allocations: Vec<_> = transactions
+      .iter()
+      .flat_map(|t| analytics.allocations(t))
+      .filter(|a| a.flow == Flow::Spending)
+      .collect();
+
+    self
+      .budgets
+      .iter()
+      .map(|budget| {
+        let (start, end) = budget.range(as_of);
+        let spent: f64 = allocations
+          .iter()
+          .filter(|a| {
+            let date = a.transaction.transaction_date;
+            date >= start && date <= end.min(as_of)
+          })
+          .filter(|a| match budget.target {
+            BudgetTarget::Category(id) => a.category_id == id,
+            BudgetTarget::Tag(id) => a.tag_ids.contains(&id),
+          })
+          .map(|a| a.amount)
+          .sum();
+
+        let elapsed = (as_of.min(end) - start).num_days() + 1;
+        let total = (end - start).num_days() + 1;
+        let projected = spent / elapsed as f64 * total as f64;
+
+        BudgetStatus {
+          budget: budget.clone(),
+          start,
+          end,
+          spent,
+          remaining: (budget.amount - spent).max(0.0),
+          overspent: (spent - budget.amount).max(0.0),
+          projected,
+        }
+      })
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use pretty_assertions::assert_eq;
+  use serde_json::json;
+
+  use super::*;
+  use crate::test_util::transaction;
+
+  #[test]
+  fn test_evaluate() {
+    let categories = vec![];
+    let tags = vec![];
+    let analytics = Analytics::new(&categories, &tags);
+
+    let transactions = vec![
+      transaction(json!({"amount": 100.0, "transactionDate": "2021-04-02"})),
+      transaction(json!({"amount": 50.0, "transactionDate": "2021-04-10"})),
+      transaction(json!({"amount": 20.0, "isCredit": true, "transactionDate": "2021-04-12"})),
+      transaction(json!({
+        "amount": 30.0,
+        "transactionDate": "2021-04-14",
+        "categoryId": 2,
+        "customTags": {"systemTags": [], "userTags": [9]},
+      })),
+      transaction(json!({"amount": 500.0, "transactionDate": "2021-03-31"})),
+    ];
+
+    let mut budgets = Budgets::new();
+    budgets
+      .budget(Budget::new(
+        "Groceries",
+        BudgetTarget::Category(1),
+        BudgetPeriod::Monthly,
+        200.0,
+      ))
+      .budget(Budget::new(
+        "Vacation",
+        BudgetTarget::Tag(9),
+        BudgetPeriod::Rolling(7),
+        20.0,
+      ));
+
+    let status = budgets.evaluate(&analytics, &transactions, NaiveDate::from_ymd(2021, 4, 15));
+
+    assert_eq!(status[0].spent, 130.0);
+    assert_eq!(status[0].remaining, 70.0);
+    assert_eq!(status[0].projected, 260.0);
+    assert!(!status[0].is_on_pace());
+
+    assert_eq!(status[1].start, NaiveDate::from_ymd(2021, 4, 9));
+    assert_eq!(status[1].overspent, 10.0);
+    assert!(status[1].is_over());
+  }
+}
@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+
+use crate::{
+  pc_types::{self, Currency},
+  Error,
+};
+
+// Amounts that come back without a currency are in the service default.
+const DEFAULT_CURRENCY: Currency = Currency::Usd;
+
+// Anything that carries an amount in some currency.
+pub trait Monetary {
+  fn amount(&self) -> Option<f64>;
+  fn currency(&self) -> Currency;
+}
+
+impl Monetary for pc_types::Transaction {
+  // Credits are positive and debits negative so that totals are a net amount.
+  fn amount(&self) -> Option<f64> {
+    Some(if self.is_credit {
+      self.amount
+    } else {
+      -self.amount
+    })
+  }
+
+  fn currency(&self) -> Currency {
+    self.currency
+  }
+}
+
+impl Monetary for pc_types::Account {
+  // Liability balances are what is owed, so they count against the total.
+  fn amount(&self) -> Option<f64> {
+    let balance = self.balance?;
+    Some(if self.is_liability { -balance } else { balance })
+  }
+
+  fn currency(&self) -> Currency {
+    self.currency.unwrap_or(DEFAULT_CURRENCY)
+  }
+}
+
+impl Monetary for pc_types::Holding {
+  fn amount(&self) -> Option<f64> {
+    Some(self.value)
+  }
+
+  fn currency(&self) -> Currency {
+    self.currency.unwrap_or(DEFAULT_CURRENCY)
+  }
+}
+
+// Exchange rates supplied by the caller, all relative to a single base
+// currency. A rate is how many units of the base currency one unit of the
+// other currency is worth.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateTable {
+  base: Currency,
+  rates: HashMap<Currency, f64>,
+}
+
+impl RateTable {
+  pub fn new(base: Currency) -> Self {
+    RateTable {
+      base: resolve(base),
+      rates: HashMap::new(),
+    }
+  }
+
+  pub fn base(&self) -> Currency {
+    self.base
+  }
+
+  pub fn rate(&mut self, currency: Currency, rate: f64) -> &mut Self {
+    self.rates.insert(resolve(currency), rate);
+    self
+  }
+
+  // Unknown stands for any unlisted code, so it never has a rate.
+  fn rate_of(&self, currency: Currency) -> Result<f64, Error> {
+    let currency = resolve(currency);
+    if currency == Currency::Unknown {
+      return Err(Error::MissingExchangeRate(currency));
+    }
+    if currency == self.base {
+      return Ok(1.0);
+    }
+
+    self
+      .rates
+      .get(&currency)
+      .copied()
+      .ok_or(Error::MissingExchangeRate(currency))
+  }
+
+  pub fn convert(&self, amount: f64, from: Currency, to: Currency) -> Result<f64, Error> {
+    if resolve(from) == resolve(to) && from != Currency::Unknown {
+      return Ok(amount);
+    }
+
+    Ok(amount * self.rate_of(from)? / self.rate_of(to)?)
+  }
+
+  pub fn to_base(&self, amount: f64, from: Currency) -> Result<f64, Error> {
+    self.convert(amount, from, self.base)
+  }
+
+  // Sums the amounts of all items in the base currency. Items without an amount
+  // are skipped.
+  pub fn total<'a, T, I>(&self, items: I) -> Result<f64, Error>
+  where
+    T: Monetary + 'a,
+    I: IntoIterator<Item = &'a T>,
+  {
+    items
+      .into_iter()
+      .try_fold(0.0, |total, item| match item.amount() {
+        Some(amount) => Ok(total + self.to_base(amount, item.currency())?),
+        None => Ok(total),
+      })
+  }
+}
+
+fn resolve(currency: Currency) -> Currency {
+  match currency {
+    Currency::Empty => DEFAULT_CURRENCY,
+    c => c,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use pretty_assertions::assert_eq;
+  use serde_json::json;
+
+  use super::*;
+  use crate::test_util::account;
+
+  #[test]
+  fn test_convert() {
+    let mut rates = RateTable::new(Currency::Usd);
+    rates.rate(Currency::Cad, 0.75).rate(Currency::Eur, 1.1);
+
+    assert_eq!(rates.to_base(100.0, Currency::Cad).unwrap(), 75.0);
+    assert_eq!(rates.to_base(100.0, Currency::Empty).unwrap(), 100.0);
+    assert_eq!(
+      rates.convert(110.0, Currency::Eur, Currency::Cad).unwrap(),
+      110.0 * 1.1 / 0.75
+    );
+    assert!(matches!(
+      rates.to_base(1.0, Currency::Gbp),
+      Err(Error::MissingExchangeRate(Currency::Gbp))
+    ));
+
+    let unknown: Currency = serde_json::from_str("\"BTC\"").unwrap();
+    assert_eq!(unknown, Currency::Unknown);
+    assert_eq!(
+      serde_json::from_str::<Currency>("\"cad\"").unwrap(),
+      Currency::Cad
+    );
+    for currency in [Currency::Cad, Currency::Empty, Currency::Unknown].iter() {
+      let json = serde_json::to_string(currency).unwrap();
+      assert_eq!(json, format!("\"{}\"", currency));
+    }
+    rates.rate(Currency::Unknown, 2.0);
+    assert!(matches!(
+      rates.to_base(1.0, unknown),
+      Err(Error::MissingExchangeRate(Currency::Unknown))
+    ));
+    assert!(rates
+      .convert(1.0, Currency::Unknown, Currency::Unknown)
+      .is_err());
+  }
+
+  #[test]
+  fn test_total() {
+    let mut rates = RateTable::new(Currency::Usd);
+    rates.rate(Currency::Cad, 0.75);
+
+    let accounts = vec![
+      account(json!({"balance": 1000.0})),
+      account(json!({"balance": 400.0, "currency": "CAD"})),
+      account(json!({
+        "balance": 250.0,
+        "isAsset": false,
+        "isLiability": true,
+        "accountType": "Credit",
+        "accountTypeGroup": "CREDIT_CARD",
+      })),
+      account(json!({"balance": null})),
+    ];
+    assert_eq!(rates.total(&accounts).unwrap(), 1000.0 + 300.0 - 250.0);
+  }
+}
@@ -0,0 +1,208 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::pc_types;
+
+// A pair of transactions that look like the same purchase. `duplicate` is the
+// one that would be marked; `original` is the one with the lower id.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicateCandidate {
+  pub original: i64,
+  pub duplicate: i64,
+  // 0 to 1, higher is more likely.
+  pub confidence: f64,
+  pub days_apart: i64,
+  pub same_account: bool,
+}
+
+// Finds transactions with the same amount, close dates and similar
+// descriptions. Transactions already marked as duplicates are skipped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicateDetector {
+  max_days: i64,
+  min_confidence: f64,
+  same_account: bool,
+}
+
+impl Default for DuplicateDetector {
+  fn default() -> Self {
+    DuplicateDetector {
+      max_days: 3,
+      min_confidence: 0.5,
+      same_account: false,
+    }
+  }
+}
+
+impl DuplicateDetector {
+  pub fn new() -> Self {
+    DuplicateDetector::default()
+  }
+
+  // How many days apart two transactions may be. Defaults to 3.
+  pub fn max_days(&mut self, value: i64) -> &mut Self {
+    self.max_days = value.max(0);
+    self
+  }
+
+  // Candidates scoring lower than this are dropped. Defaults to 0.5.
+  pub fn min_confidence(&mut self, value: f64) -> &mut Self {
+    self.min_confidence = value;
+    self
+  }
+
+  // Also pair transactions on the same account. Aggregation duplicates usually
+  // come from a second linked account, while two identical charges on one
+  // account are more often two real purchases, so this is off by default.
+  pub fn same_account(&mut self, value: bool) -> &mut Self {
+    self.same_account = value;
+    self
+  }
+
+  // Returns candidates with the most likely first. Each transaction is the
+  // duplicate in at most one pair.
+  pub fn detect(&self, transactions: &[pc_types::Transaction]) -> Vec<DuplicateCandidate> {
+    let mut groups: HashMap<_, Vec<&pc_types::Transaction>> = HashMap::new();
+    for t in transactions.iter().filter(|t| !t.is_duplicate) {
+      let cents = (t.amount * 100.0).round() as i64;
+      groups
+        .entry((cents, t.is_credit, t.currency.as_ref()))
+        .or_default()
+        .push(t);
+    }
+
+    let mut candidates = vec![];
+    for group in groups.values() {
+      for (i, a) in group.iter().enumerate() {
+        for b in &group[i + 1..] {
+          if let Some(c) = self.score(a, b) {
+            candidates.push(c);
+          }
+        }
+      }
+    }
+
+    candidates.sort_by(|a, b| {
+      b.confidence
+        .partial_cmp(&a.confidence)
+        .unwrap_or(std::cmp::Ordering::Equal)
+        .then(a.duplicate.cmp(&b.duplicate))
+    });
+
+    let mut seen = HashSet::new();
+    candidates.retain(|c| seen.insert(c.duplicate));
+    candidates
+  }
+
+  fn score(
+    &self,
+    a: &pc_types::Transaction,
+    b: &pc_types::Transaction,
+  ) -> Option<DuplicateCandidate> {
+    if a.user_transaction_id == b.user_transaction_id {
+      return None;
+    }
+
+    let same_account = a.user_account_id == b.user_account_id;
+    if same_account && !self.same_account {
+      return None;
+    }
+
+    let days_apart = (a.transaction_date - b.transaction_date).num_days().abs();
+    if days_apart > self.max_days {
+      return None;
+    }
+
+    let similarity = similarity(&a.description, &b.description)
+      .max(similarity(&a.original_description, &b.original_description));
+    let closeness = 1.0 - days_apart as f64 / (self.max_days + 1) as f64;
+    let confidence = 0.6 * similarity + 0.3 * closeness + if same_account { 0.0 } else { 0.1 };
+    if confidence < self.min_confidence {
+      return None;
+    }
+
+    let (original, duplicate) = if a.user_transaction_id < b.user_transaction_id {
+      (a, b)
+    } else {
+      (b, a)
+    };
+
+    Some(DuplicateCandidate {
+      original: original.user_transaction_id,
+      duplicate: duplicate.user_transaction_id,
+      confidence,
+      days_apart,
+      same_account,
+    })
+  }
+}
+
+fn words(s: &str) -> HashSet<String> {
+  s.split(|c: char| !c.is_alphanumeric())
+    .filter(|w| !w.is_empty())
+    .map(str::to_uppercase)
+    .collect()
+}
+
+// Share of words the two descriptions have in common.
+fn similarity(a: &str, b: &str) -> f64 {
+  let (a, b) = (words(a), words(b));
+  let union = a.union(&b).count();
+  if union == 0 {
+    return 0.0;
+  }
+
+  a.intersection(&b).count() as f64 / union as f64
+}
+
+#[cfg(test)]
+mod tests {
+  use pretty_assertions::assert_eq;
+  use serde_json::json;
+
+  use super::*;
+  use crate::test_util::transaction;
+
+  #[test]
+  fn test_detect() {
+    let transactions = vec![
+      transaction(json!({"userTransactionId": 1, "description": "Amazon Marketplace"})),
+      transaction(json!({
+        "userTransactionId": 2,
+        "userAccountId": 2,
+        "description": "AMAZON MARKETPLACE",
+        "transactionDate": "2021-01-02",
+      })),
+      transaction(json!({
+        "userTransactionId": 3,
+        "description": "Hardware Store",
+        "originalDescription": "HARDWARE STORE",
+        "transactionDate": "2021-01-03",
+      })),
+      transaction(json!({"userTransactionId": 4, "transactionDate": "2021-01-09"})),
+      transaction(json!({"userTransactionId": 5, "amount": 11.0})),
+    ];
+
+    let candidates = DuplicateDetector::new().detect(&transactions);
+    assert_eq!(candidates.len(), 1);
+    assert_eq!(candidates[0].original, 1);
+    assert_eq!(candidates[0].duplicate, 2);
+    assert!(!candidates[0].same_account);
+    assert!(candidates[0].confidence > 0.9);
+  }
+
+  #[test]
+  fn test_detect_same_account() {
+    // Two coffees on the same day.
+    let transactions = vec![
+      transaction(json!({"userTransactionId": 1})),
+      transaction(json!({"userTransactionId": 2})),
+    ];
+    assert_eq!(DuplicateDetector::new().detect(&transactions), vec![]);
+
+    let candidates = DuplicateDetector::new()
+      .same_account(true)
+      .detect(&transactions);
+    assert_eq!(candidates.len(), 1);
+    assert!(candidates[0].same_account);
+  }
+}
@@ -4,10 +4,21 @@ extern crate serde;
 extern crate serde_derive;
 extern crate serde_json;
 
-use std::{collections::HashMap, error::Error as StdError, io::Write, sync::Arc};
+use std::{
+  collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+  error::Error as StdError,
+  io::Write,
+  sync::Arc,
+  time::Duration,
+};
 
 use async_trait::async_trait;
+use chrono::NaiveDate;
 use cookie_store::CookieStore;
+use futures::{
+  future,
+  stream::{self, Stream, TryStreamExt},
+};
 use lazy_static::lazy_static;
 use regex::Regex;
 use reqwest::{
@@ -16,9 +27,19 @@ use reqwest::{
 };
 use thiserror::Error;
 
+pub mod analytics;
+pub mod budget;
+pub mod currency;
+pub mod duplicates;
+pub mod merchant;
 pub mod pc_types;
+pub mod query;
+pub mod recurring;
+pub mod rules;
 mod serde_util;
 pub mod sort;
+#[cfg(test)]
+mod test_util;
 
 const BASE_URL: &str = "https://home.personalcapital.com";
 const IDENTIFY_USER: &str = "/api/login/identifyUser";
@@ -30,12 +51,37 @@ const AUTHENTICATE_EMAIL: &str = "/api/credential/authenticateEmailByCode";
 const AUTHENTICATE_PASSWORD: &str = "/api/credential/authenticatePassword";
 const USER_TRANSACTIONS: &str = "/api/transaction/getUserTransactions";
 const USER_SPENDING: &str = "/api/account/getUserSpending";
+const UPDATE_USER_SPENDING: &str = "/api/account/updateUserSpending";
 const ACCOUNTS: &str = "/api/newaccount/getAccounts2";
+const CREATE_ACCOUNT: &str = "/api/newaccount/createAccounts2";
+const UPDATE_ACCOUNT: &str = "/api/newaccount/updateAccount";
+const CLOSE_ACCOUNTS: &str = "/api/newaccount/closeAccounts2";
+const REFRESH_ACCOUNTS: &str = "/api/account/refreshAccounts";
 const CATEGORIES: &str = "/api/transactioncategory/getCategories";
+const CREATE_CATEGORY: &str = "/api/transactioncategory/createCategory";
+const UPDATE_CATEGORY: &str = "/api/transactioncategory/updateCategory";
+const DELETE_CATEGORY: &str = "/api/transactioncategory/deleteCategory";
 const HOLDINGS: &str = "/api/invest/getHoldings";
+const ADD_HOLDING: &str = "/api/invest/addHolding";
+const UPDATE_HOLDING: &str = "/api/invest/updateHolding";
+const DELETE_HOLDING: &str = "/api/invest/deleteHolding";
 const TAGS: &str = "/api/transactiontag/getTags";
+const CREATE_TAG: &str = "/api/transactiontag/createTag";
+const UPDATE_TAG: &str = "/api/transactiontag/updateTag";
+const DELETE_TAG: &str = "/api/transactiontag/deleteTag";
 const UPDATE_USER_TRANSACTIONS: &str = "/api/transaction/updateUserTransactions2";
+const SPLIT_USER_TRANSACTION: &str = "/api/transaction/splitUserTransaction";
+const UNSPLIT_USER_TRANSACTION: &str = "/api/transaction/unsplitUserTransaction";
 const HISTORIES: &str = "/api/account/getHistories";
+const PERFORMANCE_HISTORIES: &str = "/api/account/getPerformanceHistories";
+const RETIREMENT_PLANNER: &str = "/api/invest/getRetirementPlanner";
+const RETIREMENT_FORECAST: &str = "/api/invest/getRetirementForecast";
+const PERSON: &str = "/api/person/getPerson";
+const HOUSEHOLDS: &str = "/api/delegate/getHouseholds";
+const SWITCH_HOUSEHOLD: &str = "/api/delegate/switchHousehold";
+
+// How often accounts() is polled while waiting for a refresh to finish.
+const DEFAULT_REFRESH_POLL_INTERVAL: Duration = Duration::from_secs(5);
 
 lazy_static! {
   static ref CSRF_RE: Regex = Regex::new(r"csrf ?= ?'([a-f0-9-]+)'").unwrap();
@@ -67,6 +113,44 @@ pub enum Error {
   AccountIDsEmpty,
   #[error("username {0} is inactive")]
   InactiveUser(String),
+  #[error("tag name is empty")]
+  TagNameEmpty,
+  #[error("category name is empty")]
+  CategoryNameEmpty,
+  #[error("custom categories can't be of type {0:?}")]
+  InvalidCategoryType(pc_types::CategoryType),
+  #[error("category {0} already exists")]
+  CategoryExists(String),
+  #[error("category {0} not found")]
+  CategoryNotFound(i64),
+  #[error("category {0} is not an editable custom category")]
+  CategoryNotEditable(i64),
+  #[error("a split needs at least two parts")]
+  SplitTooFewParts,
+  #[error("split amounts add up to {actual} but the transaction amount is {expected}")]
+  SplitAmountMismatch { expected: f64, actual: f64 },
+  #[error("account {0} not found")]
+  AccountNotFound(i64),
+  #[error("created account {0} not found")]
+  CreatedAccountNotFound(String),
+  #[error("account {0} is not a manual account")]
+  NotManualAccount(i64),
+  #[error("account {0} is not a manual portfolio")]
+  NotManualPortfolio(i64),
+  #[error("holding {0} was not entered manually")]
+  NotManualHolding(String),
+  #[error("a holding needs a ticker or a description")]
+  HoldingNameMissing,
+  #[error("timed out waiting for accounts to refresh")]
+  RefreshTimeout,
+  #[error("spending targets can only be set for MONTH or YEAR, not {0:?}")]
+  InvalidSpendingInterval(pc_types::Interval),
+  #[error("session is not a delegate session")]
+  NotDelegate,
+  #[error("invalid sort spec: {0}")]
+  InvalidSortSpec(String),
+  #[error("no exchange rate for {0}")]
+  MissingExchangeRate(pc_types::Currency),
   #[error("reqwest error")]
   Reqwest(#[from] reqwest::Error),
   #[error("cookie store error")]
@@ -135,12 +219,265 @@ pub struct UpdateUserTransactionsArgs {
   pub duplicate: Option<bool>,
 }
 
+fn decode_sp_data<T>(payload: &str) -> Result<T, Error>
+where
+  T: serde::de::DeserializeOwned,
+{
+  serde_json::from_str(payload).map_err(|e| {
+    let line = e.line() - 1;
+    let payload = payload
+      .to_string()
+      .split("\n")
+      .nth(line)
+      .unwrap()
+      .to_string();
+    let column = e.column() - 1;
+    let range_start = if column as isize - 20 < 0 {
+      0
+    } else {
+      column - 20
+    };
+    let range_end = if column + 20 >= payload.len() {
+      payload.len()
+    } else {
+      column + 20
+    };
+    let s = (&payload[range_start..range_end]).to_string();
+    Error::SerdeJsonContext(e, s)
+  })
+}
+
+// Looks up a category and makes sure it's one the user is allowed to change.
+fn custom_category(
+  categories: &[pc_types::Category],
+  category_id: i64,
+) -> Result<&pc_types::Category, Error> {
+  let category = categories
+    .iter()
+    .find(|c| c.transaction_category_id == category_id)
+    .ok_or(Error::CategoryNotFound(category_id))?;
+
+  if !category.is_custom || !category.is_editable {
+    return Err(Error::CategoryNotEditable(category_id));
+  }
+
+  Ok(category)
+}
+
+// A split needs at least two parts whose amounts add up to the transaction
+// amount, to the cent.
+fn validate_split(amount: f64, parts: &[SplitPart]) -> Result<(), Error> {
+  if parts.len() < 2 {
+    return Err(Error::SplitTooFewParts);
+  }
+
+  let total: f64 = parts.iter().map(|p| p.amount).sum();
+  if ((total - amount) * 100.0).round() != 0.0 {
+    return Err(Error::SplitAmountMismatch {
+      expected: amount,
+      actual: total,
+    });
+  }
+
+  Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeserializeMode {
+  // Any record that fails to parse fails the whole call.
+  Strict,
+  // Records in list responses (transactions, accounts, holdings) that fail to
+  // parse are skipped and reported through Client::skipped_records().
+  Lenient,
+}
+
+impl Default for DeserializeMode {
+  fn default() -> Self {
+    Self::Strict
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct SkippedRecord {
+  pub model: &'static str,
+  pub index: usize,
+  pub error: String,
+  pub record: serde_json::Value,
+}
+
+// Decodes a response holding a list of `I` records under `list_field`. In
+// lenient mode records that fail to parse are dropped and added to `skipped`.
+fn decode_list<T, I>(
+  payload: &str,
+  list_field: &'static str,
+  model: &'static str,
+  mode: DeserializeMode,
+  skipped: &mut Vec<SkippedRecord>,
+) -> Result<T, Error>
+where
+  T: serde::de::DeserializeOwned,
+  I: serde::de::DeserializeOwned,
+{
+  if mode == DeserializeMode::Strict {
+    return decode_sp_data(payload);
+  }
+
+  let mut value: serde_json::Value = serde_json::from_str(payload)?;
+  if let Some(serde_json::Value::Array(records)) = value.get_mut(list_field) {
+    let mut kept = Vec::with_capacity(records.len());
+    for (index, record) in records.drain(..).enumerate() {
+      match I::deserialize(&record) {
+        Ok(_) => kept.push(record),
+        Err(e) => {
+          log::warn!("skipping {} record {}: {}", model, index, e);
+          skipped.push(SkippedRecord {
+            model,
+            index,
+            error: e.to_string(),
+            record,
+          });
+        },
+      }
+    }
+    *records = kept;
+  }
+
+  Ok(serde_json::from_value(value)?)
+}
+
+// Adds the names of `fields` to those already seen for `model`, logging each
+// name the first time it shows up.
+fn merge_unknown_fields(
+  seen: &mut BTreeMap<&'static str, BTreeSet<String>>,
+  model: &'static str,
+  fields: &pc_types::UnknownFields,
+) {
+  if fields.is_empty() {
+    return;
+  }
+
+  let seen = seen.entry(model).or_default();
+  for name in fields.keys() {
+    if !seen.contains(name) {
+      log::warn!("unknown field in {}: {}", model, name);
+      seen.insert(name.clone());
+    }
+  }
+}
+
+// A manually tracked account such as a house, car or private equity stake.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreateManualAccountArgs {
+  pub name: String,
+  pub firm_name: Option<String>,
+  pub product_type: pc_types::ProductType,
+  pub balance: f64,
+  pub currency: Option<pc_types::Currency>,
+}
+
+// Fields of a user entered holding. Fields left as None are not changed when
+// updating a holding.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ManualHoldingArgs {
+  pub ticker: Option<String>,
+  pub description: Option<String>,
+  pub quantity: Option<f64>,
+  pub price: Option<f64>,
+  pub cost_basis: Option<f64>,
+}
+
+impl ManualHoldingArgs {
+  fn push_params(self, params: &mut Vec<(&'static str, String)>) {
+    if let Some(ticker) = self.ticker {
+      params.push(("ticker", ticker));
+    }
+
+    if let Some(description) = self.description {
+      params.push(("description", description));
+    }
+
+    if let Some(quantity) = self.quantity {
+      params.push(("quantity", format!("{}", quantity)));
+    }
+
+    if let Some(price) = self.price {
+      params.push(("price", format!("{}", price)));
+    }
+
+    if let Some(cost_basis) = self.cost_basis {
+      params.push(("costBasis", format!("{}", cost_basis)));
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SplitPart {
+  pub category_id: i64,
+  pub amount: f64,
+  pub tags: Option<Vec<i64>>,
+}
+
+// How user_transactions_stream() splits up a date range.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TransactionChunks {
+  pub window: chrono::Duration,
+  // Number of windows fetched at the same time.
+  pub concurrency: usize,
+}
+
+impl Default for TransactionChunks {
+  fn default() -> Self {
+    TransactionChunks {
+      window: chrono::Duration::days(90),
+      concurrency: 1,
+    }
+  }
+}
+
+impl TransactionChunks {
+  fn windows(&self, start_date: NaiveDate, end_date: NaiveDate) -> Vec<(NaiveDate, NaiveDate)> {
+    let window = self.window.max(chrono::Duration::days(1));
+
+    let mut windows = vec![];
+    let mut start = start_date;
+    while start <= end_date {
+      let end = (start + window - chrono::Duration::days(1)).min(end_date);
+      windows.push((start, end));
+      start = end + chrono::Duration::days(1);
+    }
+
+    windows
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserSpendingArgs {
+  pub intervals: Vec<pc_types::Interval>,
+  // Whether to include the per day spending amounts of each interval.
+  pub include_details: bool,
+}
+
+impl Default for UserSpendingArgs {
+  fn default() -> Self {
+    UserSpendingArgs {
+      intervals: vec![
+        pc_types::Interval::Month,
+        pc_types::Interval::Week,
+        pc_types::Interval::Year,
+      ],
+      include_details: true,
+    }
+  }
+}
+
 pub struct ClientBuilder {
   store: Arc<dyn Store<Error = SyncError>>,
   username: Option<String>,
   password: Option<String>,
   device_name: Option<String>,
   debug_writer: Option<Box<dyn Write + Send>>,
+  deserialize_mode: DeserializeMode,
+  refresh_poll_interval: Duration,
 }
 
 impl ClientBuilder {
@@ -151,6 +488,8 @@ impl ClientBuilder {
       password: None,
       device_name: None,
       debug_writer: None,
+      deserialize_mode: DeserializeMode::default(),
+      refresh_poll_interval: DEFAULT_REFRESH_POLL_INTERVAL,
     }
   }
 
@@ -179,6 +518,17 @@ impl ClientBuilder {
     self
   }
 
+  pub fn deserialize_mode(&mut self, mode: DeserializeMode) -> &mut Self {
+    self.deserialize_mode = mode;
+    self
+  }
+
+  // How often account refreshes check whether aggregation has finished.
+  pub fn refresh_poll_interval(&mut self, value: Duration) -> &mut Self {
+    self.refresh_poll_interval = value;
+    self
+  }
+
   pub async fn build(&mut self) -> Result<Client, Error> {
     if self.username.is_none() {
       return Err(Error::UsernameNotSet);
@@ -246,6 +596,11 @@ impl ClientBuilder {
       device_name: self.device_name.take().unwrap(),
       last_server_change_id: -1,
       debug_writer: self.debug_writer.take(),
+      unknown_fields: BTreeMap::new(),
+      deserialize_mode: self.deserialize_mode,
+      skipped_records: vec![],
+      is_delegate: false,
+      refresh_poll_interval: self.refresh_poll_interval,
     })
   }
 }
@@ -261,9 +616,39 @@ pub struct Client {
   device_name: String,
   last_server_change_id: i64,
   debug_writer: Option<Box<dyn Write + Send>>,
+  unknown_fields: BTreeMap<&'static str, BTreeSet<String>>,
+  deserialize_mode: DeserializeMode,
+  skipped_records: Vec<SkippedRecord>,
+  is_delegate: bool,
+  refresh_poll_interval: Duration,
 }
 
 impl Client {
+  // Returns the JSON fields that were seen during this session but aren't
+  // part of our models, keyed by model name ("Account", "Transaction",
+  // "Holding", "SpHeader").
+  pub fn unknown_fields(&self) -> &BTreeMap<&'static str, BTreeSet<String>> {
+    &self.unknown_fields
+  }
+
+  pub fn clear_unknown_fields(&mut self) {
+    self.unknown_fields.clear();
+  }
+
+  // Records dropped in DeserializeMode::Lenient since the last call to
+  // take_skipped_records().
+  pub fn skipped_records(&self) -> &[SkippedRecord] {
+    &self.skipped_records
+  }
+
+  pub fn take_skipped_records(&mut self) -> Vec<SkippedRecord> {
+    std::mem::take(&mut self.skipped_records)
+  }
+
+  fn record_unknown_fields(&mut self, model: &'static str, fields: &pc_types::UnknownFields) {
+    merge_unknown_fields(&mut self.unknown_fields, model, fields);
+  }
+
   async fn store_cookies(
     &mut self,
     url: reqwest::Url,
@@ -321,7 +706,14 @@ impl Client {
     self.add_cookie_header(req.headers_mut());
     let url = req.url().clone();
     let res = self.client.execute(req).await?;
+    self.check_response(url, res).await
+  }
 
+  async fn check_response(
+    &mut self,
+    url: reqwest::Url,
+    res: reqwest::Response,
+  ) -> Result<reqwest::Response, Error> {
     if let Err(e) = res.error_for_status_ref() {
       return Err(e.into());
     }
@@ -334,6 +726,34 @@ impl Client {
   where
     T: serde::de::DeserializeOwned,
   {
+    let sp_data = self.request_sp_data(req).await?;
+    decode_sp_data(sp_data.get())
+  }
+
+  // Like request_json(), but in lenient mode any record in the `list_field`
+  // array that fails to parse as `I` is dropped and reported through
+  // skipped_records() instead of failing the whole call.
+  async fn request_json_list<T, I>(
+    &mut self,
+    req: reqwest::Request,
+    list_field: &'static str,
+    model: &'static str,
+  ) -> Result<T, Error>
+  where
+    T: serde::de::DeserializeOwned,
+    I: serde::de::DeserializeOwned,
+  {
+    let sp_data = self.request_sp_data(req).await?;
+    decode_list::<T, I>(
+      sp_data.get(),
+      list_field,
+      model,
+      self.deserialize_mode,
+      &mut self.skipped_records,
+    )
+  }
+
+  fn write_debug_request(&mut self, req: &reqwest::Request) {
     if let Some(mut dw) = self.debug_writer.as_mut() {
       write!(
         &mut dw,
@@ -343,6 +763,13 @@ impl Client {
       )
       .unwrap();
     }
+  }
+
+  async fn request_sp_data(
+    &mut self,
+    req: reqwest::Request,
+  ) -> Result<Box<serde_json::value::RawValue>, Error> {
+    self.write_debug_request(&req);
 
     let res = match self.request(req).await {
       Ok(v) => v,
@@ -359,6 +786,13 @@ impl Client {
       },
     };
 
+    self.response_sp_data(res).await
+  }
+
+  async fn response_sp_data(
+    &mut self,
+    res: reqwest::Response,
+  ) -> Result<Box<serde_json::value::RawValue>, Error> {
     let text = res.text().await?;
 
     if let Some(mut dw) = self.debug_writer.as_mut() {
@@ -380,6 +814,10 @@ impl Client {
     }
 
     self.auth_level = json.sp_header.auth_level;
+    if let Some(is_delegate) = json.sp_header.is_delegate {
+      self.is_delegate = is_delegate;
+    }
+    self.record_unknown_fields("SpHeader", &json.sp_header.unknown_fields);
 
     // if let Some(changes) = json.sp_header.sp_data_changes {
     //   for change in changes {
@@ -408,29 +846,7 @@ impl Client {
       return Err(Error::PersonalCapital(msg.into()));
     }
 
-    let payload = json.sp_data.get();
-    serde_json::from_str(payload).map_err(|e| {
-      let line = e.line() - 1;
-      let payload = payload
-        .to_string()
-        .split("\n")
-        .nth(line)
-        .unwrap()
-        .to_string();
-      let column = e.column() - 1;
-      let range_start = if column as isize - 20 < 0 {
-        0
-      } else {
-        column - 20
-      };
-      let range_end = if column + 20 >= payload.len() {
-        payload.len()
-      } else {
-        column + 20
-      };
-      let s = (&payload[range_start..range_end]).to_string();
-      Error::SerdeJsonContext(e, s)
-    })
+    Ok(json.sp_data)
   }
 
   async fn get_csrf(&mut self) -> Result<(), Error> {
@@ -593,41 +1009,226 @@ impl Client {
     start_date: S,
     end_date: S,
   ) -> Result<pc_types::UserTransactions, Error> {
+    let req = self.user_transactions_request(start_date.into(), end_date.into(), None)?;
+    let json: pc_types::UserTransactions = self
+      .request_json_list::<_, pc_types::Transaction>(req, "transactions", "Transaction")
+      .await?;
+
+    for t in json.transactions.iter().flatten() {
+      self.record_unknown_fields("Transaction", &t.unknown_fields);
+    }
+
+    Ok(json)
+  }
+
+  // Fetches the transactions matching the query. Filters the server doesn't
+  // support are applied locally.
+  pub async fn query_transactions(
+    &mut self,
+    query: &query::TransactionQuery,
+  ) -> Result<Vec<pc_types::Transaction>, Error> {
+    let req = self.user_transactions_request(
+      query.start_date.to_string(),
+      query.end_date.to_string(),
+      query.account_ids.as_deref(),
+    )?;
+    let json: pc_types::UserTransactions = self
+      .request_json_list::<_, pc_types::Transaction>(req, "transactions", "Transaction")
+      .await?;
+
+    for t in json.transactions.iter().flatten() {
+      self.record_unknown_fields("Transaction", &t.unknown_fields);
+    }
+
+    Ok(
+      json
+        .transactions
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|t| query.matches(t))
+        .collect(),
+    )
+  }
+
+  fn user_transactions_request(
+    &self,
+    start_date: String,
+    end_date: String,
+    account_ids: Option<&[i64]>,
+  ) -> Result<reqwest::Request, Error> {
     let url = format!("{}{}", BASE_URL, USER_TRANSACTIONS);
 
     let mut params = HashMap::new();
     params.insert("csrf", self.csrf.clone());
     params.insert("apiClient", "WEB".into());
-    params.insert("startDate", start_date.into());
-    params.insert("endDate", end_date.into());
+    params.insert("startDate", start_date);
+    params.insert("endDate", end_date);
     params.insert(
       "lastServerChangeId",
       format!("{}", self.last_server_change_id),
     );
 
-    let req = self.client.post(&url).form(&params).build()?;
-    let json = self.request_json(req).await?;
+    if let Some(ids) = account_ids {
+      params.insert(
+        "userAccountIds",
+        format!(
+          "[{}]",
+          ids
+            .iter()
+            .map(|v| format!("{}", v))
+            .collect::<Vec<_>>()
+            .join(",")
+        ),
+      );
+    }
+
+    Ok(self.client.post(&url).form(&params).build()?)
+  }
+
+  // Fetches the transactions between two dates (inclusive) in windows of
+  // `chunks.window`, up to `chunks.concurrency` windows at a time. A transaction
+  // is only yielded once even if it shows up in more than one window.
+  pub fn user_transactions_stream(
+    &mut self,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    chunks: TransactionChunks,
+  ) -> impl Stream<Item = Result<pc_types::Transaction, Error>> + '_ {
+    let windows = chunks.windows(start_date, end_date);
+    let concurrency = chunks.concurrency.max(1);
+
+    stream::try_unfold(
+      (self, windows, HashSet::new()),
+      move |(client, mut windows, mut seen)| {
+        async move {
+          if windows.is_empty() {
+            return Ok(None);
+          }
+
+          let batch = windows
+            .drain(..concurrency.min(windows.len()))
+            .collect::<Vec<_>>();
+          let mut transactions = vec![];
+          for json in client.user_transactions_windows(&batch).await? {
+            for t in json.transactions.into_iter().flatten() {
+              if seen.insert(t.user_transaction_id) {
+                transactions.push(t);
+              }
+            }
+          }
+
+          Ok::<_, Error>(Some((transactions, (client, windows, seen))))
+        }
+      },
+    )
+    .map_ok(|transactions| stream::iter(transactions.into_iter().map(Ok)))
+    .try_flatten()
+  }
+
+  // Collects user_transactions_stream() into a single UserTransactions with the
+  // totals recomputed over the whole range.
+  pub async fn user_transactions_chunked(
+    &mut self,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    chunks: TransactionChunks,
+  ) -> Result<pc_types::UserTransactions, Error> {
+    let transactions = self
+      .user_transactions_stream(start_date, end_date, chunks)
+      .try_collect::<Vec<_>>()
+      .await?;
+
+    let mut json = pc_types::UserTransactions {
+      interval_type: None,
+      start_date,
+      end_date,
+      money_in: None,
+      money_out: None,
+      net_cashflow: None,
+      average_in: None,
+      average_out: None,
+      transactions: Some(transactions),
+    };
+    json.recompute_totals();
 
     Ok(json)
   }
 
+  // Sends one getUserTransactions request per window concurrently and returns
+  // the responses in window order.
+  async fn user_transactions_windows(
+    &mut self,
+    windows: &[(NaiveDate, NaiveDate)],
+  ) -> Result<Vec<pc_types::UserTransactions>, Error> {
+    let mut reqs = vec![];
+    for (start_date, end_date) in windows {
+      let mut req =
+        self.user_transactions_request(start_date.to_string(), end_date.to_string(), None)?;
+      self.write_debug_request(&req);
+      self.add_cookie_header(req.headers_mut());
+      reqs.push(req);
+    }
+
+    let client = &self.client;
+    let responses = future::join_all(reqs.into_iter().map(|req| {
+      let url = req.url().clone();
+      async move { (url, client.execute(req).await) }
+    }))
+    .await;
+
+    let mut out = vec![];
+    for (url, res) in responses {
+      let res = self.check_response(url, res?).await?;
+      let sp_data = self.response_sp_data(res).await?;
+      let json: pc_types::UserTransactions = decode_list::<_, pc_types::Transaction>(
+        sp_data.get(),
+        "transactions",
+        "Transaction",
+        self.deserialize_mode,
+        &mut self.skipped_records,
+      )?;
+
+      for t in json.transactions.iter().flatten() {
+        self.record_unknown_fields("Transaction", &t.unknown_fields);
+      }
+
+      out.push(json);
+    }
+
+    Ok(out)
+  }
+
   pub async fn user_spending(&mut self) -> Result<pc_types::UserSpending, Error> {
+    self.user_spending_with(&UserSpendingArgs::default()).await
+  }
+
+  pub async fn user_spending_with(
+    &mut self,
+    args: &UserSpendingArgs,
+  ) -> Result<pc_types::UserSpending, Error> {
     let url = format!("{}{}", BASE_URL, USER_SPENDING);
 
-    let params = vec![
-      ("csrf", self.csrf.clone()),
-      ("apiClient", "WEB".into()),
-      ("intervalTypes[]", "MONTH".into()),
-      ("intervalTypes[]", "WEEK".into()),
-      ("intervalTypes[]", "YEAR".into()),
-      ("includeDetails", "true".into()),
+    let mut params = vec![("csrf", self.csrf.clone()), ("apiClient", "WEB".into())];
+    for interval in &args.intervals {
+      params.push(("intervalTypes[]", interval.as_ref().into()));
+    }
+    params.extend(vec![
+      (
+        "includeDetails",
+        if args.include_details {
+          "true"
+        } else {
+          "false"
+        }
+        .into(),
+      ),
       ("includeValues[]", "CURRENT".into()),
       ("includeValues[]", "TARGET".into()),
       (
         "lastServerChangeId",
         format!("{}", self.last_server_change_id),
       ),
-    ];
+    ]);
 
     let req = self.client.post(&url).form(&params).build()?;
     let json = self.request_json(req).await?;
@@ -635,6 +1236,41 @@ impl Client {
     Ok(json)
   }
 
+  // Sets the spending target of the MONTH or YEAR interval.
+  pub async fn update_spending_target(
+    &mut self,
+    interval: pc_types::Interval,
+    target: f64,
+  ) -> Result<pc_types::UserSpending, Error> {
+    match interval {
+      pc_types::Interval::Month | pc_types::Interval::Year => {},
+      i => return Err(Error::InvalidSpendingInterval(i)),
+    }
+
+    let url = format!("{}{}", BASE_URL, UPDATE_USER_SPENDING);
+
+    let params = vec![
+      ("csrf", self.csrf.clone()),
+      ("apiClient", "WEB".into()),
+      (
+        "lastServerChangeId",
+        format!("{}", self.last_server_change_id),
+      ),
+      ("intervalType", interval.as_ref().into()),
+      ("target", format!("{}", target)),
+    ];
+
+    let req = self.client.post(&url).form(&params).build()?;
+    self.request_json::<serde::de::IgnoredAny>(req).await?;
+
+    self
+      .user_spending_with(&UserSpendingArgs {
+        intervals: vec![interval],
+        include_details: false,
+      })
+      .await
+  }
+
   pub async fn accounts(&mut self) -> Result<pc_types::Accounts, Error> {
     let url = format!("{}{}", BASE_URL, ACCOUNTS);
 
@@ -648,15 +1284,48 @@ impl Client {
     ];
 
     let req = self.client.post(&url).form(&params).build()?;
-    let json = self.request_json(req).await?;
+    let json: pc_types::Accounts = self
+      .request_json_list::<_, pc_types::Account>(req, "accounts", "Account")
+      .await?;
+
+    for a in &json.accounts {
+      self.record_unknown_fields("Account", &a.unknown_fields);
+    }
 
     Ok(json)
   }
 
-  pub async fn categories(&mut self) -> Result<pc_types::Categories, Error> {
-    let url = format!("{}{}", BASE_URL, CATEGORIES);
+  // Starts aggregation for every account and waits until none of them are
+  // aggregating anymore. Returns the refreshed accounts.
+  pub async fn refresh_all_accounts(
+    &mut self,
+    timeout: Duration,
+  ) -> Result<pc_types::Accounts, Error> {
+    self.refresh(None, timeout).await
+  }
 
-    let params = vec![
+  // Like refresh_all_accounts() but only for the accounts of the given sites.
+  pub async fn refresh_accounts(
+    &mut self,
+    user_site_ids: &[i64],
+    timeout: Duration,
+  ) -> Result<pc_types::Accounts, Error> {
+    if user_site_ids.is_empty() {
+      return Err(Error::AccountIDsEmpty);
+    }
+
+    self.refresh(Some(user_site_ids), timeout).await
+  }
+
+  async fn refresh(
+    &mut self,
+    user_site_ids: Option<&[i64]>,
+    timeout: Duration,
+  ) -> Result<pc_types::Accounts, Error> {
+    let deadline = tokio::time::Instant::now() + timeout;
+    let url = format!("{}{}", BASE_URL, REFRESH_ACCOUNTS);
+
+    let mut params = vec![
       ("csrf", self.csrf.clone()),
       ("apiClient", "WEB".into()),
       (
@@ -665,32 +1334,112 @@ impl Client {
       ),
     ];
 
+    if let Some(ids) = user_site_ids {
+      params.push((
+        "userSiteIds",
+        format!(
+          "[{}]",
+          ids
+            .iter()
+            .map(|v| format!("{}", v))
+            .collect::<Vec<_>>()
+            .join(",")
+        ),
+      ));
+    }
+
     let req = self.client.post(&url).form(&params).build()?;
-    let json = self.request_json(req).await?;
+    self.request_json::<serde::de::IgnoredAny>(req).await?;
+
+    loop {
+      // Give the server a moment; accounts don't always show as aggregating
+      // right after the refresh was requested. The last poll happens right at
+      // the deadline.
+      let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+      tokio::time::sleep(self.refresh_poll_interval.min(remaining)).await;
+
+      let accounts = self.accounts().await?;
+      let aggregating = accounts.accounts.iter().any(|a| {
+        a.aggregating
+          && user_site_ids
+            .map(|ids| ids.contains(&a.user_site_id))
+            .unwrap_or(true)
+      });
+
+      if !aggregating {
+        return Ok(accounts);
+      }
 
-    Ok(json)
+      if tokio::time::Instant::now() >= deadline {
+        return Err(Error::RefreshTimeout);
+      }
+    }
   }
 
-  pub async fn query_session(&mut self) -> Result<pc_types::QuerySession, Error> {
-    let url = format!("{}{}", BASE_URL, QUERY_SESSION);
+  pub async fn create_manual_account(
+    &mut self,
+    args: CreateManualAccountArgs,
+  ) -> Result<pc_types::Account, Error> {
+    let existing: HashSet<i64> = self
+      .accounts()
+      .await?
+      .accounts
+      .iter()
+      .filter_map(|a| a.user_account_id)
+      .collect();
+    let name = args.name.clone();
+
+    let url = format!("{}{}", BASE_URL, CREATE_ACCOUNT);
 
-    let params = vec![
+    let mut params = vec![
       ("csrf", self.csrf.clone()),
       ("apiClient", "WEB".into()),
       (
         "lastServerChangeId",
         format!("{}", self.last_server_change_id),
       ),
+      ("isManual", "true".into()),
+      ("name", args.name),
+      ("productType", args.product_type.as_ref().into()),
+      ("balance", format!("{}", args.balance)),
     ];
 
+    if let Some(firm_name) = args.firm_name {
+      params.push(("firmName", firm_name));
+    }
+
+    if let Some(currency) = args.currency {
+      params.push(("currency", currency.as_ref().into()));
+    }
+
     let req = self.client.post(&url).form(&params).build()?;
-    let json = self.request_json(req).await?;
+    self.request_json::<serde::de::IgnoredAny>(req).await?;
 
-    Ok(json)
+    // Like close_manual_account(), re-read the account rather than relying on
+    // the shape of the response. The new account is the one we didn't have.
+    self
+      .accounts()
+      .await?
+      .accounts
+      .into_iter()
+      .find(|a| {
+        a.is_manual
+          && a
+            .user_account_id
+            .map(|id| !existing.contains(&id))
+            .unwrap_or(false)
+      })
+      .ok_or(Error::CreatedAccountNotFound(name))
   }
 
-  pub async fn tags(&mut self) -> Result<pc_types::Tags, Error> {
-    let url = format!("{}{}", BASE_URL, TAGS);
+  pub async fn update_manual_account_balance(
+    &mut self,
+    user_account_id: i64,
+    balance: f64,
+  ) -> Result<pc_types::Account, Error> {
+    self.manual_account(user_account_id).await?;
+
+    let url = format!("{}{}", BASE_URL, UPDATE_ACCOUNT);
 
     let params = vec![
       ("csrf", self.csrf.clone()),
@@ -699,22 +1448,23 @@ impl Client {
         "lastServerChangeId",
         format!("{}", self.last_server_change_id),
       ),
+      ("userAccountId", format!("{}", user_account_id)),
+      ("balance", format!("{}", balance)),
     ];
 
     let req = self.client.post(&url).form(&params).build()?;
-    let json = self.request_json(req).await?;
+    self.request_json::<serde::de::IgnoredAny>(req).await?;
 
-    Ok(json)
+    self.manual_account(user_account_id).await
   }
 
-  // classifications: can be: "none", "sector", "allocation"
-  pub async fn holdings(
+  pub async fn close_manual_account(
     &mut self,
-    classifications: Option<&[&str]>,
-    account_ids: Option<&[i64]>,
-    merge_accounts: bool,
-  ) -> Result<pc_types::Holdings, Error> {
-    let url = format!("{}{}", BASE_URL, HOLDINGS);
+    user_account_id: i64,
+  ) -> Result<pc_types::Account, Error> {
+    self.manual_account(user_account_id).await?;
+
+    let url = format!("{}{}", BASE_URL, CLOSE_ACCOUNTS);
 
     let params = vec![
       ("csrf", self.csrf.clone()),
@@ -723,15 +1473,291 @@ impl Client {
         "lastServerChangeId",
         format!("{}", self.last_server_change_id),
       ),
-      (
-        "classificationStyles",
-        format!(
-          "[{}]",
+      ("userAccountIds", format!("[{}]", user_account_id)),
+    ];
+
+    let req = self.client.post(&url).form(&params).build()?;
+    self.request_json::<serde::de::IgnoredAny>(req).await?;
+
+    // Closed accounts are still returned by accounts(), now with a closed date.
+    self.manual_account(user_account_id).await
+  }
+
+  async fn account(&mut self, user_account_id: i64) -> Result<pc_types::Account, Error> {
+    self
+      .accounts()
+      .await?
+      .accounts
+      .into_iter()
+      .find(|a| a.user_account_id == Some(user_account_id))
+      .ok_or(Error::AccountNotFound(user_account_id))
+  }
+
+  async fn manual_account(&mut self, user_account_id: i64) -> Result<pc_types::Account, Error> {
+    let account = self.account(user_account_id).await?;
+
+    if !account.is_manual {
+      return Err(Error::NotManualAccount(user_account_id));
+    }
+
+    Ok(account)
+  }
+
+  pub async fn categories(&mut self) -> Result<pc_types::Categories, Error> {
+    let url = format!("{}{}", BASE_URL, CATEGORIES);
+
+    let params = vec![
+      ("csrf", self.csrf.clone()),
+      ("apiClient", "WEB".into()),
+      (
+        "lastServerChangeId",
+        format!("{}", self.last_server_change_id),
+      ),
+    ];
+
+    let req = self.client.post(&url).form(&params).build()?;
+    let json = self.request_json(req).await?;
+
+    Ok(json)
+  }
+
+  pub async fn create_category<S: Into<String>>(
+    &mut self,
+    name: S,
+    category_type: pc_types::CategoryType,
+  ) -> Result<pc_types::Categories, Error> {
+    let name = name.into();
+    if name.trim().is_empty() {
+      return Err(Error::CategoryNameEmpty);
+    }
+
+    match category_type {
+      pc_types::CategoryType::Expense | pc_types::CategoryType::Income => {},
+      t => return Err(Error::InvalidCategoryType(t)),
+    }
+
+    let categories = self.categories().await?;
+    if categories
+      .iter()
+      .any(|c| c.category_type == category_type && c.name.eq_ignore_ascii_case(name.trim()))
+    {
+      return Err(Error::CategoryExists(name));
+    }
+
+    let url = format!("{}{}", BASE_URL, CREATE_CATEGORY);
+
+    let params = vec![
+      ("csrf", self.csrf.clone()),
+      ("apiClient", "WEB".into()),
+      (
+        "lastServerChangeId",
+        format!("{}", self.last_server_change_id),
+      ),
+      ("name", name),
+      ("type", category_type.as_ref().into()),
+    ];
+
+    let req = self.client.post(&url).form(&params).build()?;
+    self.request_json::<serde::de::IgnoredAny>(req).await?;
+
+    self.categories().await
+  }
+
+  pub async fn rename_category<S: Into<String>>(
+    &mut self,
+    category_id: i64,
+    name: S,
+  ) -> Result<pc_types::Categories, Error> {
+    let name = name.into();
+    if name.trim().is_empty() {
+      return Err(Error::CategoryNameEmpty);
+    }
+
+    let categories = self.categories().await?;
+    let category_type = custom_category(&categories, category_id)?.category_type;
+    if categories.iter().any(|c| {
+      c.category_type == category_type
+        && c.transaction_category_id != category_id
+        && c.name.eq_ignore_ascii_case(name.trim())
+    }) {
+      return Err(Error::CategoryExists(name));
+    }
+
+    let url = format!("{}{}", BASE_URL, UPDATE_CATEGORY);
+
+    let params = vec![
+      ("csrf", self.csrf.clone()),
+      ("apiClient", "WEB".into()),
+      (
+        "lastServerChangeId",
+        format!("{}", self.last_server_change_id),
+      ),
+      ("transactionCategoryId", format!("{}", category_id)),
+      ("name", name),
+      ("type", category_type.as_ref().into()),
+    ];
+
+    let req = self.client.post(&url).form(&params).build()?;
+    self.request_json::<serde::de::IgnoredAny>(req).await?;
+
+    self.categories().await
+  }
+
+  pub async fn delete_category(&mut self, category_id: i64) -> Result<pc_types::Categories, Error> {
+    custom_category(&self.categories().await?, category_id)?;
+
+    let url = format!("{}{}", BASE_URL, DELETE_CATEGORY);
+
+    let params = vec![
+      ("csrf", self.csrf.clone()),
+      ("apiClient", "WEB".into()),
+      (
+        "lastServerChangeId",
+        format!("{}", self.last_server_change_id),
+      ),
+      ("transactionCategoryId", format!("{}", category_id)),
+    ];
+
+    let req = self.client.post(&url).form(&params).build()?;
+    self.request_json::<serde::de::IgnoredAny>(req).await?;
+
+    self.categories().await
+  }
+
+  pub async fn query_session(&mut self) -> Result<pc_types::QuerySession, Error> {
+    let url = format!("{}{}", BASE_URL, QUERY_SESSION);
+
+    let params = vec![
+      ("csrf", self.csrf.clone()),
+      ("apiClient", "WEB".into()),
+      (
+        "lastServerChangeId",
+        format!("{}", self.last_server_change_id),
+      ),
+    ];
+
+    let req = self.client.post(&url).form(&params).build()?;
+    let json = self.request_json(req).await?;
+
+    Ok(json)
+  }
+
+  pub async fn tags(&mut self) -> Result<pc_types::Tags, Error> {
+    let url = format!("{}{}", BASE_URL, TAGS);
+
+    let params = vec![
+      ("csrf", self.csrf.clone()),
+      ("apiClient", "WEB".into()),
+      (
+        "lastServerChangeId",
+        format!("{}", self.last_server_change_id),
+      ),
+    ];
+
+    let req = self.client.post(&url).form(&params).build()?;
+    let json = self.request_json(req).await?;
+
+    Ok(json)
+  }
+
+  pub async fn create_tag<S: Into<String>>(&mut self, name: S) -> Result<pc_types::Tags, Error> {
+    let name = name.into();
+    if name.trim().is_empty() {
+      return Err(Error::TagNameEmpty);
+    }
+
+    let url = format!("{}{}", BASE_URL, CREATE_TAG);
+
+    let params = vec![
+      ("csrf", self.csrf.clone()),
+      ("apiClient", "WEB".into()),
+      (
+        "lastServerChangeId",
+        format!("{}", self.last_server_change_id),
+      ),
+      ("tagName", name),
+    ];
+
+    let req = self.client.post(&url).form(&params).build()?;
+    self.request_json::<serde::de::IgnoredAny>(req).await?;
+
+    self.tags().await
+  }
+
+  pub async fn rename_tag<S: Into<String>>(
+    &mut self,
+    tag_id: i64,
+    name: S,
+  ) -> Result<pc_types::Tags, Error> {
+    let name = name.into();
+    if name.trim().is_empty() {
+      return Err(Error::TagNameEmpty);
+    }
+
+    let url = format!("{}{}", BASE_URL, UPDATE_TAG);
+
+    let params = vec![
+      ("csrf", self.csrf.clone()),
+      ("apiClient", "WEB".into()),
+      (
+        "lastServerChangeId",
+        format!("{}", self.last_server_change_id),
+      ),
+      ("tagId", format!("{}", tag_id)),
+      ("tagName", name),
+    ];
+
+    let req = self.client.post(&url).form(&params).build()?;
+    self.request_json::<serde::de::IgnoredAny>(req).await?;
+
+    self.tags().await
+  }
+
+  pub async fn delete_tag(&mut self, tag_id: i64) -> Result<pc_types::Tags, Error> {
+    let url = format!("{}{}", BASE_URL, DELETE_TAG);
+
+    let params = vec![
+      ("csrf", self.csrf.clone()),
+      ("apiClient", "WEB".into()),
+      (
+        "lastServerChangeId",
+        format!("{}", self.last_server_change_id),
+      ),
+      ("tagId", format!("{}", tag_id)),
+    ];
+
+    let req = self.client.post(&url).form(&params).build()?;
+    self.request_json::<serde::de::IgnoredAny>(req).await?;
+
+    self.tags().await
+  }
+
+  pub async fn holdings(
+    &mut self,
+    classifications: Option<&[pc_types::ClassificationStyle]>,
+    account_ids: Option<&[i64]>,
+    merge_accounts: bool,
+  ) -> Result<pc_types::Holdings, Error> {
+    let url = format!("{}{}", BASE_URL, HOLDINGS);
+
+    let params = vec![
+      ("csrf", self.csrf.clone()),
+      ("apiClient", "WEB".into()),
+      (
+        "lastServerChangeId",
+        format!("{}", self.last_server_change_id),
+      ),
+      (
+        "classificationStyles",
+        format!(
+          "[{}]",
           classifications
+            .filter(|v| !v.is_empty())
             .map(|v| {
               v.iter()
-                .skip(1)
-                .fold(format!("\"{}\"", v[0]), |a, b| format!("{},\"{}\"", a, b))
+                .map(|c| format!("\"{}\"", c.as_ref()))
+                .collect::<Vec<_>>()
+                .join(",")
             })
             .unwrap_or_else(|| String::from("\"none\""))
         )
@@ -748,21 +1774,116 @@ impl Client {
           account_ids
             .map(|v| {
               v.iter()
-                .skip(1)
-                .fold(format!("{}", v[0]), |a, b| format!("{},{}", a, b))
+                .map(|id| format!("{}", id))
+                .collect::<Vec<_>>()
+                .join(",")
             })
-            .unwrap_or_else(|| String::new())
+            .unwrap_or_default()
         )
         .into(),
       ),
     ];
 
     let req = self.client.post(&url).form(&params).build()?;
-    let json = self.request_json(req).await?;
+    let json: pc_types::Holdings = self
+      .request_json_list::<_, pc_types::Holding>(req, "holdings", "Holding")
+      .await?;
+
+    for h in &json.holdings {
+      self.record_unknown_fields("Holding", &h.unknown_fields);
+    }
+
+    Ok(json)
+  }
+
+  pub async fn add_manual_holding(
+    &mut self,
+    user_account_id: i64,
+    args: ManualHoldingArgs,
+  ) -> Result<pc_types::Holding, Error> {
+    if args.ticker.is_none() && args.description.is_none() {
+      return Err(Error::HoldingNameMissing);
+    }
+
+    if !self.account(user_account_id).await?.is_manual_portfolio {
+      return Err(Error::NotManualPortfolio(user_account_id));
+    }
+
+    let url = format!("{}{}", BASE_URL, ADD_HOLDING);
+
+    let mut params = vec![
+      ("csrf", self.csrf.clone()),
+      ("apiClient", "WEB".into()),
+      (
+        "lastServerChangeId",
+        format!("{}", self.last_server_change_id),
+      ),
+      ("userAccountId", format!("{}", user_account_id)),
+      ("source", "USER".into()),
+    ];
+    args.push_params(&mut params);
+
+    let req = self.client.post(&url).form(&params).build()?;
+    let json: pc_types::Holding = self.request_json(req).await?;
+    self.record_unknown_fields("Holding", &json.unknown_fields);
+
+    Ok(json)
+  }
+
+  pub async fn update_manual_holding(
+    &mut self,
+    holding: &pc_types::Holding,
+    args: ManualHoldingArgs,
+  ) -> Result<pc_types::Holding, Error> {
+    if holding.source != pc_types::HoldingSource::User {
+      return Err(Error::NotManualHolding(holding.source_asset_id.clone()));
+    }
+
+    let url = format!("{}{}", BASE_URL, UPDATE_HOLDING);
+
+    let mut params = vec![
+      ("csrf", self.csrf.clone()),
+      ("apiClient", "WEB".into()),
+      (
+        "lastServerChangeId",
+        format!("{}", self.last_server_change_id),
+      ),
+      ("userAccountId", format!("{}", holding.user_account_id)),
+      ("sourceAssetId", holding.source_asset_id.clone()),
+    ];
+    args.push_params(&mut params);
+
+    let req = self.client.post(&url).form(&params).build()?;
+    let json: pc_types::Holding = self.request_json(req).await?;
+    self.record_unknown_fields("Holding", &json.unknown_fields);
 
     Ok(json)
   }
 
+  pub async fn delete_manual_holding(&mut self, holding: &pc_types::Holding) -> Result<(), Error> {
+    if holding.source != pc_types::HoldingSource::User {
+      return Err(Error::NotManualHolding(holding.source_asset_id.clone()));
+    }
+
+    let url = format!("{}{}", BASE_URL, DELETE_HOLDING);
+
+    let params = vec![
+      ("csrf", self.csrf.clone()),
+      ("apiClient", "WEB".into()),
+      (
+        "lastServerChangeId",
+        format!("{}", self.last_server_change_id),
+      ),
+      ("userAccountId", format!("{}", holding.user_account_id)),
+      ("sourceAssetId", holding.source_asset_id.clone()),
+    ];
+
+    let req = self.client.post(&url).form(&params).build()?;
+    self.request_json::<serde::de::IgnoredAny>(req).await?;
+
+    Ok(())
+  }
+
   pub async fn update_user_transactions(
     &mut self,
     mut args: UpdateUserTransactionsArgs,
@@ -829,7 +1950,122 @@ impl Client {
     }
 
     let req = self.client.post(&url).form(&params).build()?;
-    let json = self.request_json(req).await?;
+    let json: Vec<pc_types::Transaction> = self.request_json(req).await?;
+
+    for t in &json {
+      self.record_unknown_fields("Transaction", &t.unknown_fields);
+    }
+
+    Ok(json)
+  }
+
+  // Marks the duplicate side of each confirmed candidate in a single update.
+  pub async fn mark_duplicates(
+    &mut self,
+    candidates: &[duplicates::DuplicateCandidate],
+  ) -> Result<Vec<pc_types::Transaction>, Error> {
+    let mut transaction_ids: Vec<i64> = candidates.iter().map(|c| c.duplicate).collect();
+    transaction_ids.sort_unstable();
+    transaction_ids.dedup();
+
+    if transaction_ids.is_empty() {
+      return Ok(vec![]);
+    }
+
+    self
+      .update_user_transactions(UpdateUserTransactionsArgs {
+        transaction_ids,
+        duplicate: Some(true),
+        ..Default::default()
+      })
+      .await
+  }
+
+  // Runs the rules over the transactions and sends the resulting changes, one
+  // update per distinct set of values. Use Rules::dry_run() to preview them.
+  pub async fn apply_rules(
+    &mut self,
+    rules: &rules::Rules,
+    transactions: &[pc_types::Transaction],
+  ) -> Result<Vec<pc_types::Transaction>, Error> {
+    let mut updated = vec![];
+    for args in rules::batch_changes(&rules.dry_run(transactions)) {
+      updated.extend(self.update_user_transactions(args).await?);
+    }
+
+    Ok(updated)
+  }
+
+  // Splits a transaction into parts with their own category and tags. The part
+  // amounts have to add up to the transaction amount.
+  pub async fn split_transaction(
+    &mut self,
+    transaction: &pc_types::Transaction,
+    parts: &[SplitPart],
+  ) -> Result<Vec<pc_types::Transaction>, Error> {
+    validate_split(transaction.amount, parts)?;
+
+    let url = format!("{}{}", BASE_URL, SPLIT_USER_TRANSACTION);
+
+    let splits = parts
+      .iter()
+      .map(|p| {
+        serde_json::json!({
+          "amount": p.amount,
+          "categoryId": p.category_id,
+          "customTags": {
+            "userTags": p.tags.clone().unwrap_or_default(),
+          },
+        })
+      })
+      .collect::<Vec<_>>();
+
+    let params = vec![
+      ("csrf", self.csrf.clone()),
+      ("apiClient", "WEB".into()),
+      (
+        "lastServerChangeId",
+        format!("{}", self.last_server_change_id),
+      ),
+      (
+        "userTransactionId",
+        format!("{}", transaction.user_transaction_id),
+      ),
+      ("splits", serde_json::to_string(&splits)?),
+    ];
+
+    let req = self.client.post(&url).form(&params).build()?;
+    let json: Vec<pc_types::Transaction> = self.request_json(req).await?;
+
+    for t in &json {
+      self.record_unknown_fields("Transaction", &t.unknown_fields);
+    }
+
+    Ok(json)
+  }
+
+  pub async fn unsplit_transaction(
+    &mut self,
+    transaction_id: i64,
+  ) -> Result<Vec<pc_types::Transaction>, Error> {
+    let url = format!("{}{}", BASE_URL, UNSPLIT_USER_TRANSACTION);
+
+    let params = vec![
+      ("csrf", self.csrf.clone()),
+      ("apiClient", "WEB".into()),
+      (
+        "lastServerChangeId",
+        format!("{}", self.last_server_change_id),
+      ),
+      ("userTransactionId", format!("{}", transaction_id)),
+    ];
+
+    let req = self.client.post(&url).form(&params).build()?;
+    let json: Vec<pc_types::Transaction> = self.request_json(req).await?;
+
+    for t in &json {
+      self.record_unknown_fields("Transaction", &t.unknown_fields);
+    }
 
     Ok(json)
   }
@@ -902,4 +2138,332 @@ impl Client {
 
     Ok(json)
   }
+
+  pub async fn performance_histories<S: Into<String>>(
+    &mut self,
+    account_ids: Option<&[i64]>,
+    start_date: S,
+    end_date: S,
+    interval: pc_types::Interval,
+    benchmarks: Option<&[pc_types::Benchmark]>,
+  ) -> Result<pc_types::PerformanceHistories, Error> {
+    let url = format!("{}{}", BASE_URL, PERFORMANCE_HISTORIES);
+
+    let params = vec![
+      ("csrf", self.csrf.clone()),
+      ("apiClient", "WEB".into()),
+      (
+        "lastServerChangeId",
+        format!("{}", self.last_server_change_id),
+      ),
+      ("startDate", start_date.into()),
+      ("endDate", end_date.into()),
+      ("interval", interval.as_ref().into()),
+      ("intervalType", interval.as_ref().into()),
+      (
+        "benchmarkIds",
+        format!(
+          "[{}]",
+          benchmarks
+            .map(|v| {
+              v.iter()
+                .map(|b| format!("\"{}\"", b.as_ref()))
+                .collect::<Vec<_>>()
+                .join(",")
+            })
+            .unwrap_or_default()
+        ),
+      ),
+      (
+        "userAccountIds",
+        format!(
+          "[{}]",
+          account_ids
+            .map(|v| {
+              v.iter()
+                .map(|id| format!("{}", id))
+                .collect::<Vec<_>>()
+                .join(",")
+            })
+            .unwrap_or_default()
+        ),
+      ),
+    ];
+
+    let req = self.client.post(&url).form(&params).build()?;
+    let json = self.request_json(req).await?;
+
+    Ok(json)
+  }
+
+  pub async fn retirement_planner(&mut self) -> Result<pc_types::RetirementPlanner, Error> {
+    let url = format!("{}{}", BASE_URL, RETIREMENT_PLANNER);
+
+    let params = vec![
+      ("csrf", self.csrf.clone()),
+      ("apiClient", "WEB".into()),
+      (
+        "lastServerChangeId",
+        format!("{}", self.last_server_change_id),
+      ),
+    ];
+
+    let req = self.client.post(&url).form(&params).build()?;
+    let json = self.request_json(req).await?;
+
+    Ok(json)
+  }
+
+  pub async fn retirement_forecast(&mut self) -> Result<pc_types::RetirementForecast, Error> {
+    let url = format!("{}{}", BASE_URL, RETIREMENT_FORECAST);
+
+    let params = vec![
+      ("csrf", self.csrf.clone()),
+      ("apiClient", "WEB".into()),
+      (
+        "lastServerChangeId",
+        format!("{}", self.last_server_change_id),
+      ),
+    ];
+
+    let req = self.client.post(&url).form(&params).build()?;
+    let json = self.request_json(req).await?;
+
+    Ok(json)
+  }
+
+  pub async fn person(&mut self) -> Result<pc_types::Person, Error> {
+    let url = format!("{}{}", BASE_URL, PERSON);
+
+    let params = vec![
+      ("csrf", self.csrf.clone()),
+      ("apiClient", "WEB".into()),
+      (
+        "lastServerChangeId",
+        format!("{}", self.last_server_change_id),
+      ),
+    ];
+
+    let req = self.client.post(&url).form(&params).build()?;
+    let json = self.request_json(req).await?;
+
+    Ok(json)
+  }
+
+  // Lists the households a delegate session can switch between.
+  pub async fn households(&mut self) -> Result<pc_types::Households, Error> {
+    if !self.is_delegate {
+      return Err(Error::NotDelegate);
+    }
+
+    let url = format!("{}{}", BASE_URL, HOUSEHOLDS);
+
+    let params = vec![
+      ("csrf", self.csrf.clone()),
+      ("apiClient", "WEB".into()),
+      (
+        "lastServerChangeId",
+        format!("{}", self.last_server_change_id),
+      ),
+    ];
+
+    let req = self.client.post(&url).form(&params).build()?;
+    let json = self.request_json(req).await?;
+
+    Ok(json)
+  }
+
+  // Switches a delegate session to another household. All calls made
+  // afterwards return that household's data. Returns the household's profile.
+  pub async fn switch_household<S: Into<String>>(
+    &mut self,
+    user_guid: S,
+  ) -> Result<pc_types::Person, Error> {
+    if !self.is_delegate {
+      return Err(Error::NotDelegate);
+    }
+
+    let url = format!("{}{}", BASE_URL, SWITCH_HOUSEHOLD);
+
+    let params = vec![
+      ("csrf", self.csrf.clone()),
+      ("apiClient", "WEB".into()),
+      (
+        "lastServerChangeId",
+        format!("{}", self.last_server_change_id),
+      ),
+      ("userGuid", user_guid.into()),
+    ];
+
+    let req = self.client.post(&url).form(&params).build()?;
+    self.request_json::<serde::de::IgnoredAny>(req).await?;
+
+    self.person().await
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use pretty_assertions::assert_eq;
+  use serde_json::json;
+
+  use super::*;
+
+  #[test]
+  fn test_decode_list() {
+    let good = serde_json::to_value(test_util::transaction(json!({}))).unwrap();
+    let mut bad = good.clone();
+    bad["amount"] = json!("lots");
+    let payload = json!({
+      "startDate": "2021-01-01",
+      "endDate": "2021-01-31",
+      "transactions": [good, bad, good],
+    })
+    .to_string();
+
+    let mut skipped = vec![];
+    let decoded: pc_types::UserTransactions = decode_list::<_, pc_types::Transaction>(
+      &payload,
+      "transactions",
+      "Transaction",
+      DeserializeMode::Lenient,
+      &mut skipped,
+    )
+    .unwrap();
+    assert_eq!(decoded.transactions.map(|t| t.len()), Some(2));
+    assert_eq!(skipped.len(), 1);
+    assert_eq!(skipped[0].model, "Transaction");
+    assert_eq!(skipped[0].index, 1);
+    assert_eq!(skipped[0].record["amount"], json!("lots"));
+
+    let mut skipped = vec![];
+    let strict = decode_list::<pc_types::UserTransactions, pc_types::Transaction>(
+      &payload,
+      "transactions",
+      "Transaction",
+      DeserializeMode::Strict,
+      &mut skipped,
+    );
+    assert!(strict.is_err());
+    assert!(skipped.is_empty());
+  }
+
+  #[test]
+  fn test_unknown_fields() {
+    let extra = json!({"brandNewField": 1});
+    let account = test_util::account(extra.clone());
+    let transaction = test_util::transaction(extra.clone());
+    let holding = test_util::holding(extra.clone());
+    let header: pc_types::SpHeader = serde_json::from_value(json!({
+      "SP_HEADER_VERSION": 1,
+      "success": true,
+      "authLevel": "SESSION_AUTHENTICATED",
+      "status": "ACTIVE",
+      "userGuid": "guid",
+      "brandNewField": 1,
+    }))
+    .unwrap();
+
+    // Known fields, including renamed ones, don't show up.
+    let names = |fields: &pc_types::UnknownFields| fields.keys().cloned().collect::<Vec<_>>();
+    for fields in [
+      &account.unknown_fields,
+      &transaction.unknown_fields,
+      &holding.unknown_fields,
+      &header.unknown_fields,
+    ]
+    .iter()
+    {
+      assert_eq!(names(fields), vec!["brandNewField"]);
+    }
+
+    let mut seen = BTreeMap::new();
+    merge_unknown_fields(&mut seen, "Account", &account.unknown_fields);
+    merge_unknown_fields(&mut seen, "Transaction", &transaction.unknown_fields);
+    merge_unknown_fields(&mut seen, "Transaction", &transaction.unknown_fields);
+    let other = test_util::transaction(json!({"anotherField": true}));
+    merge_unknown_fields(&mut seen, "Transaction", &other.unknown_fields);
+    merge_unknown_fields(&mut seen, "Holding", &pc_types::UnknownFields::new());
+
+    let seen: Vec<_> = seen
+      .iter()
+      .map(|(model, names)| (*model, names.iter().cloned().collect::<Vec<_>>()))
+      .collect();
+    assert_eq!(
+      seen,
+      vec![
+        ("Account", vec!["brandNewField".to_string()]),
+        (
+          "Transaction",
+          vec!["anotherField".to_string(), "brandNewField".to_string()]
+        ),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_validate_split() {
+    let part = |amount: f64| SplitPart {
+      category_id: 1,
+      amount,
+      tags: None,
+    };
+
+    assert!(validate_split(30.0, &[part(10.0), part(20.0)]).is_ok());
+    // 0.1 + 0.2 isn't exactly 0.3.
+    assert!(validate_split(0.3, &[part(0.1), part(0.2)]).is_ok());
+    assert!(validate_split(10.0, &[part(3.333), part(6.666)]).is_ok());
+    assert!(matches!(
+      validate_split(30.0, &[part(10.0), part(19.0)]),
+      Err(Error::SplitAmountMismatch { expected, actual }) if expected == 30.0 && actual == 29.0
+    ));
+    assert!(matches!(
+      validate_split(30.0, &[part(10.0), part(20.01)]),
+      Err(Error::SplitAmountMismatch { .. })
+    ));
+    assert!(matches!(
+      validate_split(30.0, &[part(30.0)]),
+      Err(Error::SplitTooFewParts)
+    ));
+    assert!(matches!(
+      validate_split(0.0, &[]),
+      Err(Error::SplitTooFewParts)
+    ));
+  }
+
+  #[test]
+  fn test_transaction_chunk_windows() {
+    let d = |m, day| NaiveDate::from_ymd(2021, m, day);
+    let chunks = |days| TransactionChunks {
+      window: chrono::Duration::days(days),
+      concurrency: 1,
+    };
+
+    // The end date is inclusive and the last window is cut short.
+    assert_eq!(
+      chunks(10).windows(d(1, 1), d(1, 25)),
+      vec![
+        (d(1, 1), d(1, 10)),
+        (d(1, 11), d(1, 20)),
+        (d(1, 21), d(1, 25)),
+      ]
+    );
+    assert_eq!(
+      chunks(10).windows(d(1, 1), d(1, 10)),
+      vec![(d(1, 1), d(1, 10))]
+    );
+    assert_eq!(
+      chunks(10).windows(d(1, 5), d(1, 5)),
+      vec![(d(1, 5), d(1, 5))]
+    );
+    assert_eq!(chunks(10).windows(d(1, 6), d(1, 5)), vec![]);
+
+    // Windows shorter than a day are treated as one day.
+    for days in [0, -3].iter() {
+      assert_eq!(
+        chunks(*days).windows(d(1, 1), d(1, 3)),
+        vec![(d(1, 1), d(1, 1)), (d(1, 2), d(1, 2)), (d(1, 3), d(1, 3))]
+      );
+    }
+  }
 }
@@ -0,0 +1,156 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::pc_types;
+
+lazy_static! {
+  // Payment processors and bank wording put in front of the merchant name.
+  static ref PREFIX_RE: Regex = Regex::new(
+    r"^((SQ|SQU|TST|PP|PAYPAL|SP|IC|GOOGLE|APL|PY|BT|DD|CKE)\s*\*|(POS PURCHASE|POS|DEBIT CARD PURCHASE|DEBIT PURCHASE|CHECKCARD|CHECK CARD|RECURRING PAYMENT|ACH DEBIT|PURCHASE AUTHORIZED ON \d{2}/\d{2})(\s+\d{4})?\s+)\s*"
+  )
+  .unwrap();
+  static ref CARD_RE: Regex =
+    Regex::new(r"\bCARD\s*(ENDING\s*(IN)?)?\s*#?\s*\d{4}\b|(X{2,}|\*{2,})\d{2,}").unwrap();
+  static ref DOMAIN_RE: Regex = Regex::new(r"\.(COM|NET|ORG|CO|IO)\b").unwrap();
+  static ref NON_WORD_RE: Regex = Regex::new(r"[^A-Z0-9&]+").unwrap();
+  pub(crate) static ref DEFAULT_NORMALIZER: MerchantNormalizer = MerchantNormalizer::default();
+}
+
+const STATES: [&str; 51] = [
+  "AK", "AL", "AR", "AZ", "CA", "CO", "CT", "DC", "DE", "FL", "GA", "HI", "IA", "ID", "IL", "IN",
+  "KS", "KY", "LA", "MA", "MD", "ME", "MI", "MN", "MO", "MS", "MT", "NC", "ND", "NE", "NH", "NJ",
+  "NM", "NV", "NY", "OH", "OK", "OR", "PA", "RI", "SC", "SD", "TN", "TX", "UT", "VA", "VT", "WA",
+  "WI", "WV", "WY",
+];
+
+// Aliases every normalizer starts with.
+const DEFAULT_ALIASES: [(&str, &str); 4] = [
+  ("AMZN", "AMAZON"),
+  ("WM SUPERCENTER", "WALMART"),
+  ("WAL MART", "WALMART"),
+  ("NFLX", "NETFLIX"),
+];
+
+// The most specific merchant name the server gave us.
+pub(crate) fn merchant_name(t: &pc_types::Transaction) -> &str {
+  [t.merchant.as_deref(), t.simple_description.as_deref()]
+    .iter()
+    .flatten()
+    .map(|s| s.trim())
+    .find(|s| !s.is_empty())
+    .unwrap_or_else(|| t.description.trim())
+}
+
+// Turns merchant names such as "SQ *COFFEE SHOP 1234 SEATTLE WA" into a
+// canonical key ("COFFEE SHOP") so that the same merchant groups together.
+// This is a best-effort heuristic; aliases fix up the cases it gets wrong.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MerchantNormalizer {
+  // Alias words and the key they map to, longest first.
+  aliases: Vec<(Vec<String>, String)>,
+}
+
+impl Default for MerchantNormalizer {
+  fn default() -> Self {
+    let mut normalizer = MerchantNormalizer { aliases: vec![] };
+    for (from, to) in DEFAULT_ALIASES.iter() {
+      normalizer.alias(from, to);
+    }
+    normalizer
+  }
+}
+
+impl MerchantNormalizer {
+  pub fn new() -> Self {
+    MerchantNormalizer::default()
+  }
+
+  // Maps every name whose cleaned up words start with the words of `from` to
+  // `to`. When several aliases match, the longest one wins.
+  pub fn alias(&mut self, from: &str, to: &str) -> &mut Self {
+    let words = clean(from);
+    if !words.is_empty() {
+      self.aliases.retain(|(w, _)| *w != words);
+      self.aliases.push((words, to.trim().to_uppercase()));
+      self.aliases.sort_by_key(|(words, _)| std::cmp::Reverse(words.len()));
+    }
+    self
+  }
+
+  pub fn normalize(&self, name: &str) -> String {
+    let words = clean(name);
+    self
+      .aliases
+      .iter()
+      .find(|(alias, _)| words.starts_with(alias))
+      .map(|(_, to)| to.clone())
+      .unwrap_or_else(|| words.join(" "))
+  }
+
+  // The key for a transaction's merchant, falling back to the simple
+  // description and then the description when there is no merchant.
+  pub fn key(&self, t: &pc_types::Transaction) -> String {
+    self.normalize(merchant_name(t))
+  }
+}
+
+fn clean(name: &str) -> Vec<String> {
+  let mut s = name.trim().to_uppercase().replace('\'', "");
+  while let Some(m) = PREFIX_RE.find(&s) {
+    s = s[m.end()..].to_string();
+  }
+  let s = CARD_RE.replace_all(&s, " ");
+  let s = DOMAIN_RE.replace_all(&s, " ");
+  let s = NON_WORD_RE.replace_all(&s, " ");
+
+  let mut words: Vec<String> = s.split_whitespace().map(str::to_string).collect();
+
+  // Store numbers, dates and reference numbers come after the name and are
+  // usually followed by the location, so drop everything from there on.
+  if let Some(i) = words
+    .iter()
+    .skip(1)
+    .position(|w| w.chars().any(|c| c.is_ascii_digit()))
+  {
+    words.truncate(i + 1);
+  }
+
+  // A trailing state, and the city before it if enough is left to be a name.
+  if words.len() >= 2 && STATES.contains(&words[words.len() - 1].as_str()) {
+    words.pop();
+    if words.len() >= 3 {
+      words.pop();
+    }
+  }
+
+  words
+}
+
+#[cfg(test)]
+mod tests {
+  use pretty_assertions::assert_eq;
+
+  use super::*;
+
+  #[test]
+  fn test_normalize() {
+    let mut normalizer = MerchantNormalizer::new();
+    normalizer.alias("Blue Bottle", "Blue Bottle Coffee");
+
+    let cases = [
+      ("SQ *COFFEE SHOP 1234 SEATTLE WA", "COFFEE SHOP"),
+      ("Coffee Shop", "COFFEE SHOP"),
+      ("TST* COFFEE SHOP SEATTLE WA", "COFFEE SHOP"),
+      ("NETFLIX.COM", "NETFLIX"),
+      ("AMZN Mktp US*2K4TR0J61", "AMAZON"),
+      ("CHEVRON 0123456", "CHEVRON"),
+      ("Trader Joe's #552", "TRADER JOES"),
+      ("SPOTIFY USA", "SPOTIFY USA"),
+      ("CHECKCARD 0412 SHELL OIL 57444 WA XXXX1234", "SHELL OIL"),
+      ("BLUE BOTTLE COFFEE OAKLAND CA", "BLUE BOTTLE COFFEE"),
+    ];
+    for (name, key) in cases.iter() {
+      assert_eq!(normalizer.normalize(name), *key, "{}", name);
+    }
+  }
+}
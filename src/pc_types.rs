@@ -4,12 +4,18 @@ use chrono::{self, serde::ts_milliseconds_option, DateTime, NaiveDate, Utc};
 use serde::Deserialize;
 use serde_json::value::RawValue;
 
-use crate::serde_util::{deserialize_f64_option, deserialize_maybe_nan, empty_string_as_none};
+use crate::serde_util::{
+  deserialize_f64_option, deserialize_f64_option_map, deserialize_maybe_nan, empty_string_as_none,
+};
 
 fn empty_rawvalue() -> Box<RawValue> {
   serde_json::value::RawValue::from_string("null".into()).unwrap()
 }
 
+// Fields returned by the server that the model doesn't know about yet. These
+// are kept so that schema changes can be spotted; see Client::unknown_fields().
+pub type UnknownFields = HashMap<String, serde_json::Value>;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Response {
   #[serde(rename = "spData", default = "empty_rawvalue")]
@@ -50,6 +56,8 @@ pub struct SpHeader {
   pub errors: Option<Vec<Error>>,
   #[serde(rename = "personId")]
   pub person_id: Option<i64>,
+  #[serde(flatten)]
+  pub unknown_fields: UnknownFields,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -148,6 +156,31 @@ pub struct UserTransactions {
   pub average_in: Option<f64>,
 }
 
+impl UserTransactions {
+  // Recomputes money in/out and net cashflow from the transactions, leaving out
+  // duplicates. The averages depend on the server's interval and are cleared.
+  pub fn recompute_totals(&mut self) {
+    let transactions = self.transactions.as_deref().unwrap_or_default();
+
+    let money_in: f64 = transactions
+      .iter()
+      .filter(|t| t.is_cash_in && !t.is_duplicate)
+      .map(|t| t.amount)
+      .sum();
+    let money_out: f64 = transactions
+      .iter()
+      .filter(|t| t.is_cash_out && !t.is_duplicate)
+      .map(|t| t.amount)
+      .sum();
+
+    self.money_in = Some(money_in);
+    self.money_out = Some(money_out);
+    self.net_cashflow = Some(money_in - money_out);
+    self.average_in = None;
+    self.average_out = None;
+  }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Transaction {
   #[serde(rename = "isInterest")]
@@ -227,6 +260,8 @@ pub struct Transaction {
   pub original_category_id: Option<i64>,
   #[serde(rename = "customTags")]
   pub custom_tags: Option<CustomTags>,
+  #[serde(flatten)]
+  pub unknown_fields: UnknownFields,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -426,7 +461,7 @@ pub struct SpendingInterval {
   pub average: Option<f64>,
   #[serde(rename = "current")]
   pub current: f64,
-  #[serde(rename = "details")]
+  #[serde(rename = "details", default)]
   pub details: Vec<SpendingDetail>,
   #[serde(rename = "target")]
   pub target: f64,
@@ -603,8 +638,12 @@ pub struct Account {
   pub is_tax_deferred_or_non_taxable: Option<bool>,
   #[serde(rename = "lastPaymentDate", with = "ts_milliseconds_option", default)]
   pub last_payment_date: Option<DateTime<Utc>>,
-  #[serde(rename = "lastPaymentAmount")]
-  pub last_payment_amount: Option<AmountDue>,
+  #[serde(
+    rename = "lastPaymentAmount",
+    default,
+    deserialize_with = "deserialize_f64_option"
+  )]
+  pub last_payment_amount: Option<f64>,
   pub currency: Option<Currency>,
   #[serde(rename = "pcbEnrollmentState")]
   pub pcb_enrollment_state: Option<String>,
@@ -612,8 +651,12 @@ pub struct Account {
   pub product_type: Option<ProductType>,
   #[serde(rename = "isAccountNumberValidated")]
   pub is_account_number_validated: Option<bool>,
-  #[serde(rename = "minPaymentDue")]
-  pub min_payment_due: Option<AmountDue>,
+  #[serde(
+    rename = "minPaymentDue",
+    default,
+    deserialize_with = "deserialize_f64_option"
+  )]
+  pub min_payment_due: Option<f64>,
   #[serde(rename = "accountTypeNew", default)]
   pub account_type_new: AccountTypeNew,
   #[serde(rename = "isLiability")]
@@ -622,8 +665,12 @@ pub struct Account {
   pub is_transfer_eligible: Option<bool>,
   #[serde(rename = "creditUtilization")]
   pub credit_utilization: Option<f64>,
-  #[serde(rename = "amountDue")]
-  pub amount_due: Option<AmountDue>,
+  #[serde(
+    rename = "amountDue",
+    default,
+    deserialize_with = "deserialize_f64_option"
+  )]
+  pub amount_due: Option<f64>,
   #[serde(rename = "isEsog")]
   pub is_esog: bool,
   #[serde(rename = "createdDate", with = "ts_milliseconds_option", default)]
@@ -712,6 +759,8 @@ pub struct Account {
   pub total_fee: Option<f64>,
   #[serde(rename = "disbursementType")]
   pub disbursement_type: Option<String>,
+  #[serde(flatten)]
+  pub unknown_fields: UnknownFields,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
@@ -900,17 +949,258 @@ impl Default for AccountTypeGroup {
   }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
-pub enum AmountDue {
-  NaN,
+// Defines Currency and its code lookups from a single list, so that adding a
+// currency is a one line change.
+macro_rules! currencies {
+  ($($variant:ident => $code:literal,)*) => {
+    // ISO 4217 currency codes. Empty is what the server sends when it doesn't
+    // know, Unknown stands in for any code not listed here (such as crypto);
+    // see crate::currency for converting between them.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub enum Currency {
+      Empty,
+      $($variant,)*
+      Unknown,
+    }
+
+    impl AsRef<str> for Currency {
+      fn as_ref(&self) -> &'static str {
+        match self {
+          Self::Empty => "",
+          $(Self::$variant => $code,)*
+          Self::Unknown => "UNKNOWN",
+        }
+      }
+    }
+
+    impl Currency {
+      // Looks up a known code. Codes are matched case-insensitively.
+      pub fn from_code(code: &str) -> Option<Self> {
+        Some(match code.trim().to_uppercase().as_str() {
+          "" => Self::Empty,
+          $($code => Self::$variant,)*
+          _ => return None,
+        })
+      }
+    }
+  };
+}
+
+currencies! {
+  Aed => "AED",
+  Afn => "AFN",
+  All => "ALL",
+  Amd => "AMD",
+  Ang => "ANG",
+  Aoa => "AOA",
+  Ars => "ARS",
+  Aud => "AUD",
+  Awg => "AWG",
+  Azn => "AZN",
+  Bam => "BAM",
+  Bbd => "BBD",
+  Bdt => "BDT",
+  Bgn => "BGN",
+  Bhd => "BHD",
+  Bif => "BIF",
+  Bmd => "BMD",
+  Bnd => "BND",
+  Bob => "BOB",
+  Bov => "BOV",
+  Brl => "BRL",
+  Bsd => "BSD",
+  Btn => "BTN",
+  Bwp => "BWP",
+  Byn => "BYN",
+  Bzd => "BZD",
+  Cad => "CAD",
+  Cdf => "CDF",
+  Che => "CHE",
+  Chf => "CHF",
+  Chw => "CHW",
+  Clf => "CLF",
+  Clp => "CLP",
+  Cny => "CNY",
+  Cop => "COP",
+  Cou => "COU",
+  Crc => "CRC",
+  Cuc => "CUC",
+  Cup => "CUP",
+  Cve => "CVE",
+  Czk => "CZK",
+  Djf => "DJF",
+  Dkk => "DKK",
+  Dop => "DOP",
+  Dzd => "DZD",
+  Egp => "EGP",
+  Ern => "ERN",
+  Etb => "ETB",
+  Eur => "EUR",
+  Fjd => "FJD",
+  Fkp => "FKP",
+  Gbp => "GBP",
+  Gel => "GEL",
+  Ghs => "GHS",
+  Gip => "GIP",
+  Gmd => "GMD",
+  Gnf => "GNF",
+  Gtq => "GTQ",
+  Gyd => "GYD",
+  Hkd => "HKD",
+  Hnl => "HNL",
+  Hrk => "HRK",
+  Htg => "HTG",
+  Huf => "HUF",
+  Idr => "IDR",
+  Ils => "ILS",
+  Inr => "INR",
+  Iqd => "IQD",
+  Irr => "IRR",
+  Isk => "ISK",
+  Jmd => "JMD",
+  Jod => "JOD",
+  Jpy => "JPY",
+  Kes => "KES",
+  Kgs => "KGS",
+  Khr => "KHR",
+  Kmf => "KMF",
+  Kpw => "KPW",
+  Krw => "KRW",
+  Kwd => "KWD",
+  Kyd => "KYD",
+  Kzt => "KZT",
+  Lak => "LAK",
+  Lbp => "LBP",
+  Lkr => "LKR",
+  Lrd => "LRD",
+  Lsl => "LSL",
+  Lyd => "LYD",
+  Mad => "MAD",
+  Mdl => "MDL",
+  Mga => "MGA",
+  Mkd => "MKD",
+  Mmk => "MMK",
+  Mnt => "MNT",
+  Mop => "MOP",
+  Mru => "MRU",
+  Mur => "MUR",
+  Mvr => "MVR",
+  Mwk => "MWK",
+  Mxn => "MXN",
+  Mxv => "MXV",
+  Myr => "MYR",
+  Mzn => "MZN",
+  Nad => "NAD",
+  Ngn => "NGN",
+  Nio => "NIO",
+  Nok => "NOK",
+  Npr => "NPR",
+  Nzd => "NZD",
+  Omr => "OMR",
+  Pab => "PAB",
+  Pen => "PEN",
+  Pgk => "PGK",
+  Php => "PHP",
+  Pkr => "PKR",
+  Pln => "PLN",
+  Pyg => "PYG",
+  Qar => "QAR",
+  Ron => "RON",
+  Rsd => "RSD",
+  Rub => "RUB",
+  Rwf => "RWF",
+  Sar => "SAR",
+  Sbd => "SBD",
+  Scr => "SCR",
+  Sdg => "SDG",
+  Sek => "SEK",
+  Sgd => "SGD",
+  Shp => "SHP",
+  Sle => "SLE",
+  Sll => "SLL",
+  Sos => "SOS",
+  Srd => "SRD",
+  Ssp => "SSP",
+  Stn => "STN",
+  Svc => "SVC",
+  Syp => "SYP",
+  Szl => "SZL",
+  Thb => "THB",
+  Tjs => "TJS",
+  Tmt => "TMT",
+  Tnd => "TND",
+  Top => "TOP",
+  Try => "TRY",
+  Ttd => "TTD",
+  Twd => "TWD",
+  Tzs => "TZS",
+  Uah => "UAH",
+  Ugx => "UGX",
+  Usd => "USD",
+  Usn => "USN",
+  Uyi => "UYI",
+  Uyu => "UYU",
+  Uyw => "UYW",
+  Uzs => "UZS",
+  Ved => "VED",
+  Ves => "VES",
+  Vnd => "VND",
+  Vuv => "VUV",
+  Wst => "WST",
+  Xaf => "XAF",
+  Xag => "XAG",
+  Xau => "XAU",
+  Xba => "XBA",
+  Xbb => "XBB",
+  Xbc => "XBC",
+  Xbd => "XBD",
+  Xcd => "XCD",
+  Xdr => "XDR",
+  Xof => "XOF",
+  Xpd => "XPD",
+  Xpf => "XPF",
+  Xpt => "XPT",
+  Xsu => "XSU",
+  Xts => "XTS",
+  Xua => "XUA",
+  Xxx => "XXX",
+  Yer => "YER",
+  Zar => "ZAR",
+  Zmw => "ZMW",
+  Zwl => "ZWL",
+}
+
+impl std::str::FromStr for Currency {
+  type Err = serde_json::Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Self::from_code(s)
+      .ok_or_else(|| serde::de::Error::custom(format!("unknown currency code {:?}", s)))
+  }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
-pub enum Currency {
-  #[serde(rename = "")]
-  Empty,
-  #[serde(rename = "USD")]
-  Usd,
+impl serde::Serialize for Currency {
+  fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(self.as_ref())
+  }
+}
+
+// Codes that aren't listed deserialize to Unknown rather than failing the
+// whole record.
+impl<'de> Deserialize<'de> for Currency {
+  fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    let code = String::deserialize(deserializer)?;
+    Ok(Self::from_code(&code).unwrap_or_else(|| {
+      log::warn!("unknown currency code {:?}", code);
+      Self::Unknown
+    }))
+  }
+}
+
+impl std::fmt::Display for Currency {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    f.write_str(self.as_ref())
+  }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
@@ -1049,6 +1339,19 @@ pub enum ProductType {
   OtherAssets,
 }
 
+impl AsRef<str> for ProductType {
+  fn as_ref(&self) -> &'static str {
+    match self {
+      Self::Bank => "BANK",
+      Self::CreditCard => "CREDIT_CARD",
+      Self::Investment => "INVESTMENT",
+      Self::Mortgage => "MORTGAGE",
+      Self::Loan => "LOAN",
+      Self::OtherAssets => "OTHER_ASSETS",
+    }
+  }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
 pub enum RoutingNumberSource {
   #[serde(rename = "YODLEE_AGGREGATION")]
@@ -1092,10 +1395,22 @@ pub enum CategoryType {
   DeferredCompensation,
 }
 
+impl AsRef<str> for CategoryType {
+  fn as_ref(&self) -> &'static str {
+    match self {
+      Self::Expense => "EXPENSE",
+      Self::Income => "INCOME",
+      Self::Transfer => "TRANSFER",
+      Self::Uncategorized => "UNCATEGORIZED",
+      Self::DeferredCompensation => "DEFERRED_COMPENSATION",
+    }
+  }
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct Holdings {
   #[serde(rename = "classifications")]
-  pub classifications: Vec<Option<serde_json::Value>>,
+  pub classifications: Vec<Option<Classification>>,
   #[serde(rename = "holdings")]
   pub holdings: Vec<Holding>,
   #[serde(rename = "holdingsTotalValue")]
@@ -1143,7 +1458,7 @@ pub struct Holding {
   #[serde(rename = "valueSortIndex")]
   pub value_sort_index: i64,
   #[serde(rename = "currency")]
-  pub currency: Option<String>,
+  pub currency: Option<Currency>,
   #[serde(rename = "value")]
   pub value: f64,
   #[serde(rename = "oneDayPercentChange")]
@@ -1174,6 +1489,124 @@ pub struct Holding {
   pub fees_per_year: Option<f64>,
   #[serde(rename = "costBasis")]
   pub cost_basis: Option<f64>,
+  #[serde(flatten)]
+  pub unknown_fields: UnknownFields,
+}
+
+impl Holding {
+  // Gain over the cost basis, if the cost basis is known.
+  pub fn unrealized_gain(&self) -> Option<f64> {
+    Some(self.value - self.cost_basis?)
+  }
+
+  pub fn unrealized_gain_percent(&self) -> Option<f64> {
+    let cost_basis = self.cost_basis.filter(|v| *v != 0.0)?;
+    Some(self.unrealized_gain()? / cost_basis * 100.0)
+  }
+}
+
+impl Holdings {
+  // Returns the top level classification tree for the given style, if it was
+  // requested from holdings().
+  pub fn classification(&self, style: ClassificationStyle) -> Option<&Classification> {
+    self
+      .classifications
+      .iter()
+      .flatten()
+      .find(|c| c.classification_type_name.as_deref() == Some(style.as_ref()))
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum ClassificationStyle {
+  #[serde(rename = "none")]
+  None,
+  #[serde(rename = "sector")]
+  Sector,
+  #[serde(rename = "allocation")]
+  Allocation,
+}
+
+impl AsRef<str> for ClassificationStyle {
+  fn as_ref(&self) -> &'static str {
+    match self {
+      Self::None => "none",
+      Self::Sector => "sector",
+      Self::Allocation => "allocation",
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Classification {
+  #[serde(rename = "classificationTypeName")]
+  pub classification_type_name: Option<String>,
+  #[serde(rename = "classificationTypeDisplayName")]
+  pub classification_type_display_name: Option<String>,
+  #[serde(rename = "classificationName")]
+  pub classification_name: Option<String>,
+  #[serde(rename = "classificationDisplayName")]
+  pub classification_display_name: Option<String>,
+  #[serde(default, deserialize_with = "deserialize_f64_option")]
+  pub value: Option<f64>,
+  #[serde(
+    alias = "percentOfTotal",
+    default,
+    deserialize_with = "deserialize_f64_option"
+  )]
+  pub percentage: Option<f64>,
+  #[serde(default)]
+  pub classifications: Vec<Classification>,
+  #[serde(default)]
+  pub holdings: Vec<ClassificationHolding>,
+  #[serde(flatten)]
+  pub unknown_fields: UnknownFields,
+}
+
+impl Classification {
+  pub fn name(&self) -> Option<&str> {
+    self
+      .classification_display_name
+      .as_deref()
+      .or(self.classification_name.as_deref())
+      .or(self.classification_type_display_name.as_deref())
+      .or(self.classification_type_name.as_deref())
+  }
+
+  // Returns the classifications at the bottom of the tree, i.e. the ones that
+  // hold the member holdings.
+  pub fn leaves(&self) -> Vec<&Classification> {
+    if self.classifications.is_empty() {
+      return vec![self];
+    }
+
+    self
+      .classifications
+      .iter()
+      .flat_map(Classification::leaves)
+      .collect()
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ClassificationHolding {
+  #[serde(rename = "userAccountId")]
+  pub user_account_id: Option<i64>,
+  pub ticker: Option<String>,
+  pub cusip: Option<String>,
+  pub description: Option<String>,
+  #[serde(default, deserialize_with = "deserialize_f64_option")]
+  pub quantity: Option<f64>,
+  #[serde(default, deserialize_with = "deserialize_f64_option")]
+  pub value: Option<f64>,
+  #[serde(
+    alias = "percentOfTotal",
+    default,
+    deserialize_with = "deserialize_f64_option"
+  )]
+  pub percentage: Option<f64>,
+  #[serde(flatten)]
+  pub unknown_fields: UnknownFields,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
@@ -1238,11 +1671,20 @@ pub enum HoldingSource {
   Yodlee,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
-#[serde(untagged)]
-pub enum HistoryBalance {
-  Float(f64),
-  String(String),
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(transparent)]
+pub struct UserAccountId(pub i64);
+
+impl From<i64> for UserAccountId {
+  fn from(id: i64) -> Self {
+    Self(id)
+  }
+}
+
+impl std::fmt::Display for UserAccountId {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    write!(f, "{}", self.0)
+  }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
@@ -1333,7 +1775,10 @@ pub struct History {
   pub aggregate_daily_change_amount: Option<f64>,
   #[serde(rename = "aggregateCashIn")]
   pub aggregate_cash_in: Option<f64>,
-  pub balances: HashMap<String, HistoryBalance>,
+  // Balances keyed by user account id. None means the server had no balance
+  // for the account on this date.
+  #[serde(default, deserialize_with = "deserialize_f64_option_map")]
+  pub balances: HashMap<String, Option<f64>>,
   #[serde(rename = "aggregateBalance")]
   pub aggregate_balance: f64,
   #[serde(rename = "aggregateCashOut")]
@@ -1347,6 +1792,34 @@ pub struct History {
   pub aggregate_expense: Option<i64>,
 }
 
+impl History {
+  pub fn balance(&self, account: UserAccountId) -> Option<f64> {
+    self
+      .balances
+      .get(&account.to_string())
+      .copied()
+      .flatten()
+  }
+
+  // Returns the available balances keyed by account; accounts without a balance
+  // on this date are left out.
+  pub fn account_balances(&self) -> HashMap<UserAccountId, f64> {
+    self
+      .balances
+      .iter()
+      .filter_map(|(id, balance)| Some((UserAccountId(id.parse().ok()?), (*balance)?)))
+      .collect()
+  }
+
+  pub fn daily_change(&self, account: UserAccountId) -> Option<f64> {
+    self
+      .daily_change_amount
+      .as_ref()?
+      .get(&account.to_string())
+      .copied()
+  }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Cashflow {
   pub income: f64,
@@ -1394,3 +1867,466 @@ pub struct OneDaySummaries {
   #[serde(rename = "aggregatedOneDayValueChange")]
   pub aggregated_one_day_value_change: i64,
 }
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Benchmark {
+  #[serde(rename = "^INX")]
+  SP500,
+  #[serde(rename = "^DJI")]
+  DowJones,
+  #[serde(rename = "^IXIC")]
+  Nasdaq,
+  #[serde(rename = "^RUT")]
+  Russell2000,
+  #[serde(rename = "^MSCIEAFE")]
+  MsciEafe,
+  #[serde(rename = "^AGG")]
+  UsBonds,
+  #[serde(other)]
+  Other,
+}
+
+impl AsRef<str> for Benchmark {
+  fn as_ref(&self) -> &'static str {
+    match self {
+      Self::SP500 => "^INX",
+      Self::DowJones => "^DJI",
+      Self::Nasdaq => "^IXIC",
+      Self::Russell2000 => "^RUT",
+      Self::MsciEafe => "^MSCIEAFE",
+      Self::UsBonds => "^AGG",
+      Self::Other => "",
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PerformanceHistories {
+  #[serde(rename = "intervalType")]
+  pub interval_type: Option<Interval>,
+  #[serde(rename = "accountSummaries", default)]
+  pub account_summaries: Vec<AccountPerformance>,
+  #[serde(rename = "benchmarkSummaries", default)]
+  pub benchmark_summaries: Vec<BenchmarkPerformance>,
+  #[serde(default)]
+  pub histories: Vec<PerformanceHistory>,
+  #[serde(flatten)]
+  pub unknown_fields: UnknownFields,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AccountPerformance {
+  #[serde(rename = "userAccountId")]
+  pub user_account_id: i64,
+  #[serde(rename = "accountName")]
+  pub account_name: Option<String>,
+  #[serde(rename = "siteName")]
+  pub site_name: Option<String>,
+  #[serde(
+    rename = "currentBalance",
+    default,
+    deserialize_with = "deserialize_f64_option"
+  )]
+  pub current_balance: Option<f64>,
+  // Time weighted return over the requested date range, as a percentage.
+  #[serde(
+    rename = "dateRangePerformancePercentageChange",
+    default,
+    deserialize_with = "deserialize_f64_option"
+  )]
+  pub date_range_performance_percentage_change: Option<f64>,
+  #[serde(
+    rename = "dateRangePerformanceValueChange",
+    default,
+    deserialize_with = "deserialize_f64_option"
+  )]
+  pub date_range_performance_value_change: Option<f64>,
+  #[serde(
+    rename = "oneDayPerformanceValueChange",
+    default,
+    deserialize_with = "deserialize_f64_option"
+  )]
+  pub one_day_performance_value_change: Option<f64>,
+  #[serde(flatten)]
+  pub unknown_fields: UnknownFields,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct BenchmarkPerformance {
+  #[serde(rename = "benchmarkId")]
+  pub benchmark: Benchmark,
+  #[serde(rename = "benchmarkName")]
+  pub name: Option<String>,
+  #[serde(
+    rename = "dateRangePerformancePercentageChange",
+    default,
+    deserialize_with = "deserialize_f64_option"
+  )]
+  pub date_range_performance_percentage_change: Option<f64>,
+  #[serde(flatten)]
+  pub unknown_fields: UnknownFields,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PerformanceHistory {
+  pub date: chrono::NaiveDate,
+  // Cumulative return of all requested accounts up to this date, as a
+  // percentage.
+  #[serde(
+    rename = "aggregatePerformance",
+    default,
+    deserialize_with = "deserialize_f64_option"
+  )]
+  pub aggregate_performance: Option<f64>,
+  // Cumulative returns keyed by user account id.
+  #[serde(default, deserialize_with = "deserialize_f64_option_map")]
+  pub performances: HashMap<String, Option<f64>>,
+  // Cumulative returns keyed by benchmark id, e.g. "^INX".
+  #[serde(default, deserialize_with = "deserialize_f64_option_map")]
+  pub benchmarks: HashMap<String, Option<f64>>,
+}
+
+impl PerformanceHistory {
+  pub fn performance(&self, account: UserAccountId) -> Option<f64> {
+    self
+      .performances
+      .get(&account.to_string())
+      .copied()
+      .flatten()
+  }
+
+  pub fn benchmark(&self, benchmark: Benchmark) -> Option<f64> {
+    self.benchmarks.get(benchmark.as_ref()).copied().flatten()
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RetirementPlanner {
+  #[serde(rename = "currentAge")]
+  pub current_age: Option<i64>,
+  #[serde(rename = "retirementAge")]
+  pub retirement_age: Option<i64>,
+  #[serde(rename = "lifeExpectancy")]
+  pub life_expectancy: Option<i64>,
+  #[serde(
+    rename = "annualRetirementSpending",
+    default,
+    deserialize_with = "deserialize_f64_option"
+  )]
+  pub annual_retirement_spending: Option<f64>,
+  #[serde(rename = "incomeEvents", default)]
+  pub income_events: Vec<IncomeEvent>,
+  #[serde(rename = "spendingGoals", default)]
+  pub spending_goals: Vec<SpendingGoal>,
+  #[serde(flatten)]
+  pub unknown_fields: UnknownFields,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct IncomeEvent {
+  pub id: Option<i64>,
+  pub name: Option<String>,
+  // e.g. "SOCIAL_SECURITY", "PENSION", "RENTAL", "OTHER"
+  #[serde(rename = "type")]
+  pub income_type: Option<String>,
+  #[serde(default, deserialize_with = "deserialize_f64_option")]
+  pub amount: Option<f64>,
+  #[serde(rename = "startAge")]
+  pub start_age: Option<i64>,
+  #[serde(rename = "endAge")]
+  pub end_age: Option<i64>,
+  #[serde(
+    rename = "startDate",
+    deserialize_with = "empty_string_as_none",
+    default
+  )]
+  pub start_date: Option<NaiveDate>,
+  #[serde(rename = "endDate", deserialize_with = "empty_string_as_none", default)]
+  pub end_date: Option<NaiveDate>,
+  #[serde(rename = "isTaxable")]
+  pub is_taxable: Option<bool>,
+  #[serde(rename = "isInflationAdjusted")]
+  pub is_inflation_adjusted: Option<bool>,
+  #[serde(flatten)]
+  pub unknown_fields: UnknownFields,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SpendingGoal {
+  pub id: Option<i64>,
+  pub name: Option<String>,
+  // e.g. "HOME", "EDUCATION", "TRAVEL", "OTHER"
+  #[serde(rename = "type")]
+  pub goal_type: Option<String>,
+  #[serde(default, deserialize_with = "deserialize_f64_option")]
+  pub amount: Option<f64>,
+  #[serde(rename = "startAge")]
+  pub start_age: Option<i64>,
+  #[serde(rename = "endAge")]
+  pub end_age: Option<i64>,
+  #[serde(
+    rename = "startDate",
+    deserialize_with = "empty_string_as_none",
+    default
+  )]
+  pub start_date: Option<NaiveDate>,
+  #[serde(rename = "endDate", deserialize_with = "empty_string_as_none", default)]
+  pub end_date: Option<NaiveDate>,
+  #[serde(rename = "isRecurring")]
+  pub is_recurring: Option<bool>,
+  #[serde(flatten)]
+  pub unknown_fields: UnknownFields,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RetirementForecast {
+  // Share of Monte Carlo simulations in which the money lasts, as a
+  // percentage.
+  #[serde(
+    rename = "probabilityOfSuccess",
+    default,
+    deserialize_with = "deserialize_f64_option"
+  )]
+  pub probability_of_success: Option<f64>,
+  #[serde(rename = "simulationCount")]
+  pub simulation_count: Option<i64>,
+  #[serde(default)]
+  pub projections: Vec<ForecastYear>,
+  #[serde(flatten)]
+  pub unknown_fields: UnknownFields,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ForecastYear {
+  pub year: i64,
+  pub age: Option<i64>,
+  #[serde(default, deserialize_with = "deserialize_f64_option")]
+  pub income: Option<f64>,
+  #[serde(default, deserialize_with = "deserialize_f64_option")]
+  pub spending: Option<f64>,
+  // Projected balance in the 10th, 50th and 90th percentile simulations.
+  #[serde(
+    rename = "percentile10",
+    default,
+    deserialize_with = "deserialize_f64_option"
+  )]
+  pub percentile_10: Option<f64>,
+  #[serde(
+    rename = "percentile50",
+    default,
+    deserialize_with = "deserialize_f64_option"
+  )]
+  pub percentile_50: Option<f64>,
+  #[serde(
+    rename = "percentile90",
+    default,
+    deserialize_with = "deserialize_f64_option"
+  )]
+  pub percentile_90: Option<f64>,
+  #[serde(flatten)]
+  pub unknown_fields: UnknownFields,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Person {
+  #[serde(rename = "personId")]
+  pub person_id: Option<i64>,
+  #[serde(rename = "userGuid")]
+  pub user_guid: Option<String>,
+  pub name: Option<PersonName>,
+  #[serde(rename = "birthday", deserialize_with = "empty_string_as_none", default)]
+  pub birth_date: Option<NaiveDate>,
+  #[serde(rename = "emailAddress")]
+  pub email_address: Option<String>,
+  #[serde(rename = "maritalStatus")]
+  pub marital_status: Option<String>,
+  #[serde(rename = "householdMembers", default)]
+  pub household_members: Vec<HouseholdMember>,
+  pub advisor: Option<Advisor>,
+  #[serde(flatten)]
+  pub unknown_fields: UnknownFields,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PersonName {
+  #[serde(rename = "firstName")]
+  pub first_name: Option<String>,
+  #[serde(rename = "middleName")]
+  pub middle_name: Option<String>,
+  #[serde(rename = "lastName")]
+  pub last_name: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct HouseholdMember {
+  #[serde(rename = "personId")]
+  pub person_id: Option<i64>,
+  // e.g. "SPOUSE", "CHILD"
+  pub relationship: Option<String>,
+  pub name: Option<PersonName>,
+  #[serde(rename = "birthday", deserialize_with = "empty_string_as_none", default)]
+  pub birth_date: Option<NaiveDate>,
+  #[serde(flatten)]
+  pub unknown_fields: UnknownFields,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Advisor {
+  #[serde(rename = "advisorId")]
+  pub advisor_id: Option<i64>,
+  pub name: Option<String>,
+  pub email: Option<String>,
+  pub phone: Option<String>,
+  #[serde(flatten)]
+  pub unknown_fields: UnknownFields,
+}
+
+pub type Households = Vec<Household>;
+
+// A household a delegate session has access to.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Household {
+  #[serde(rename = "userGuid")]
+  pub user_guid: String,
+  #[serde(rename = "personId")]
+  pub person_id: Option<i64>,
+  pub name: Option<String>,
+  #[serde(rename = "isCurrent", default)]
+  pub is_current: bool,
+  #[serde(flatten)]
+  pub unknown_fields: UnknownFields,
+}
+
+#[cfg(test)]
+mod tests {
+  use pretty_assertions::assert_eq;
+  use serde_json::json;
+
+  use super::*;
+  use crate::test_util::{holding, transaction};
+
+  #[test]
+  fn test_recompute_totals() {
+    let cash_in = |amount: f64| json!({"amount": amount, "isCredit": true, "isCashIn": true, "isCashOut": false});
+
+    // Transactions from two chunks merged together, with server totals that
+    // only covered the first chunk.
+    let mut totals: UserTransactions = serde_json::from_value(json!({
+      "startDate": "2021-01-01",
+      "endDate": "2021-06-30",
+      "moneyIn": 100.0,
+      "moneyOut": 10.0,
+      "netCashflow": 90.0,
+      "averageIn": 50.0,
+      "averageOut": 5.0,
+      "transactions": [],
+    }))
+    .unwrap();
+    totals.transactions = Some(vec![
+      transaction(cash_in(100.0)),
+      transaction(json!({"amount": 10.0})),
+      transaction(cash_in(250.0)),
+      transaction(json!({"amount": 40.5, "transactionDate": "2021-05-01"})),
+      transaction(json!({"amount": 40.5, "isDuplicate": true})),
+      transaction(
+        json!({"amount": 999.0, "isCredit": true, "isCashIn": true, "isDuplicate": true}),
+      ),
+      // Neither cash in nor cash out, e.g. an investment transaction.
+      transaction(json!({"amount": 5.0, "isCashOut": false})),
+    ]);
+
+    totals.recompute_totals();
+    assert_eq!(totals.money_in, Some(350.0));
+    assert_eq!(totals.money_out, Some(50.5));
+    assert_eq!(totals.net_cashflow, Some(299.5));
+    assert_eq!(totals.average_in, None);
+    assert_eq!(totals.average_out, None);
+
+    totals.transactions = None;
+    totals.recompute_totals();
+    assert_eq!(totals.money_in, Some(0.0));
+    assert_eq!(totals.net_cashflow, Some(0.0));
+  }
+
+  #[test]
+  fn test_holdings_classification() {
+    let vti = serde_json::to_value(holding(json!({"ticker": "VTI", "value": 300.0}))).unwrap();
+    let xle = serde_json::to_value(holding(json!({"ticker": "XLE", "value": 100.0}))).unwrap();
+    let holdings: Holdings = serde_json::from_value(json!({
+      "holdings": [vti, xle],
+      "holdingsTotalValue": 400.0,
+      "classifications": [
+        null,
+        {
+          "classificationTypeName": "allocation",
+          "value": 400.0,
+          "classifications": [],
+        },
+        {
+          "classificationTypeName": "sector",
+          "classificationTypeDisplayName": "Sector",
+          "value": "400.00",
+          "percentOfTotal": 100.0,
+          "classifications": [
+            {
+              "classificationName": "technology",
+              "classificationDisplayName": "Technology",
+              "value": 300.0,
+              "percentage": 75.0,
+              "holdings": [{"ticker": "VTI", "userAccountId": 1, "value": 300.0, "percentOfTotal": 75.0}],
+            },
+            {
+              "classificationName": "energy",
+              "value": 100.0,
+              "percentage": 25.0,
+              "classifications": [
+                {
+                  "classificationName": "oilAndGas",
+                  "classificationDisplayName": "Oil & Gas",
+                  "value": 100.0,
+                  "holdings": [{"ticker": "XLE", "userAccountId": 1, "value": 100.0}],
+                },
+              ],
+            },
+          ],
+        },
+      ],
+    }))
+    .unwrap();
+    assert_eq!(holdings.holdings.len(), 2);
+    assert!(holdings.classification(ClassificationStyle::None).is_none());
+
+    let sector = holdings
+      .classification(ClassificationStyle::Sector)
+      .unwrap();
+    assert_eq!(sector.name(), Some("Sector"));
+    assert_eq!(sector.value, Some(400.0));
+    assert_eq!(sector.percentage, Some(100.0));
+
+    let leaves: Vec<_> = sector
+      .leaves()
+      .iter()
+      .map(|c| {
+        let tickers: Vec<_> = c
+          .holdings
+          .iter()
+          .filter_map(|h| h.ticker.as_deref())
+          .collect();
+        (c.name(), c.percentage, tickers)
+      })
+      .collect();
+    assert_eq!(
+      leaves,
+      vec![
+        (Some("Technology"), Some(75.0), vec!["VTI"]),
+        (Some("Oil & Gas"), None, vec!["XLE"]),
+      ]
+    );
+    assert_eq!(sector.classifications[0].holdings[0].percentage, Some(75.0));
+
+    let allocation = holdings
+      .classification(ClassificationStyle::Allocation)
+      .unwrap();
+    assert_eq!(allocation.leaves().len(), 1);
+  }
+}
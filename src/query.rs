@@ -0,0 +1,217 @@
+use chrono::NaiveDate;
+use regex::Regex;
+
+use crate::pc_types;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Duplicates {
+  Include,
+  Exclude,
+  Only,
+}
+
+impl Default for Duplicates {
+  fn default() -> Self {
+    Self::Include
+  }
+}
+
+// Filters for Client::query_transactions(). The date range and account ids are
+// sent to the server; everything else is applied to the returned transactions.
+#[derive(Debug, Clone)]
+pub struct TransactionQuery {
+  pub(crate) start_date: NaiveDate,
+  pub(crate) end_date: NaiveDate,
+  pub(crate) account_ids: Option<Vec<i64>>,
+  category_ids: Option<Vec<i64>>,
+  tag_ids: Option<Vec<i64>>,
+  statuses: Option<Vec<pc_types::TransactionStatus>>,
+  transaction_types: Option<Vec<pc_types::TransactionType>>,
+  min_amount: Option<f64>,
+  max_amount: Option<f64>,
+  description: Option<Regex>,
+  credit: Option<bool>,
+  duplicates: Duplicates,
+}
+
+impl TransactionQuery {
+  pub fn new(start_date: NaiveDate, end_date: NaiveDate) -> Self {
+    TransactionQuery {
+      start_date,
+      end_date,
+      account_ids: None,
+      category_ids: None,
+      tag_ids: None,
+      statuses: None,
+      transaction_types: None,
+      min_amount: None,
+      max_amount: None,
+      description: None,
+      credit: None,
+      duplicates: Duplicates::default(),
+    }
+  }
+
+  pub fn account_ids(&mut self, value: &[i64]) -> &mut Self {
+    self.account_ids = Some(value.to_vec());
+    self
+  }
+
+  // Matches the transaction category or the category of any of its splits.
+  pub fn category_ids(&mut self, value: &[i64]) -> &mut Self {
+    self.category_ids = Some(value.to_vec());
+    self
+  }
+
+  // Matches if the transaction or any of its splits has one of the tags.
+  pub fn tag_ids(&mut self, value: &[i64]) -> &mut Self {
+    self.tag_ids = Some(value.to_vec());
+    self
+  }
+
+  pub fn statuses(&mut self, value: &[pc_types::TransactionStatus]) -> &mut Self {
+    self.statuses = Some(value.to_vec());
+    self
+  }
+
+  pub fn transaction_types(&mut self, value: &[pc_types::TransactionType]) -> &mut Self {
+    self.transaction_types = Some(value.to_vec());
+    self
+  }
+
+  // Inclusive bounds on the (unsigned) transaction amount.
+  pub fn min_amount(&mut self, value: f64) -> &mut Self {
+    self.min_amount = Some(value);
+    self
+  }
+
+  pub fn max_amount(&mut self, value: f64) -> &mut Self {
+    self.max_amount = Some(value);
+    self
+  }
+
+  // Matched against both the description and the original description.
+  pub fn description(&mut self, value: Regex) -> &mut Self {
+    self.description = Some(value);
+    self
+  }
+
+  // true for credits only, false for debits only.
+  pub fn credit(&mut self, value: bool) -> &mut Self {
+    self.credit = Some(value);
+    self
+  }
+
+  pub fn duplicates(&mut self, value: Duplicates) -> &mut Self {
+    self.duplicates = value;
+    self
+  }
+
+  pub fn matches(&self, t: &pc_types::Transaction) -> bool {
+    if t.transaction_date < self.start_date || t.transaction_date > self.end_date {
+      return false;
+    }
+
+    if let Some(ids) = &self.account_ids {
+      if !ids.contains(&t.user_account_id) {
+        return false;
+      }
+    }
+
+    if let Some(ids) = &self.category_ids {
+      let split_match = t
+        .splits
+        .iter()
+        .flatten()
+        .any(|s| ids.contains(&s.category_id));
+      if !ids.contains(&t.category_id) && !split_match {
+        return false;
+      }
+    }
+
+    if let Some(ids) = &self.tag_ids {
+      let tags = t
+        .custom_tags
+        .iter()
+        .chain(t.splits.iter().flatten().filter_map(|s| s.custom_tags.as_ref()))
+        .flat_map(|c| c.user_tags.iter().chain(c.system_tags.iter()));
+      if !tags.into_iter().any(|id| ids.contains(id)) {
+        return false;
+      }
+    }
+
+    if let Some(statuses) = &self.statuses {
+      if !statuses.contains(&t.status) {
+        return false;
+      }
+    }
+
+    if let Some(types) = &self.transaction_types {
+      if !types.contains(&t.transaction_type) {
+        return false;
+      }
+    }
+
+    if matches!(self.min_amount, Some(min) if t.amount < min)
+      || matches!(self.max_amount, Some(max) if t.amount > max)
+    {
+      return false;
+    }
+
+    if let Some(re) = &self.description {
+      if !re.is_match(&t.description) && !re.is_match(&t.original_description) {
+        return false;
+      }
+    }
+
+    if let Some(credit) = self.credit {
+      if t.is_credit != credit {
+        return false;
+      }
+    }
+
+    match self.duplicates {
+      Duplicates::Include => true,
+      Duplicates::Exclude => !t.is_duplicate,
+      Duplicates::Only => t.is_duplicate,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::json;
+
+  use super::*;
+  use crate::test_util::transaction;
+
+  #[test]
+  fn test_matches() {
+    let start = NaiveDate::from_ymd(2021, 1, 1);
+    let end = NaiveDate::from_ymd(2021, 1, 31);
+
+    let t = transaction(json!({
+      "amount": 25.0,
+      "categoryId": 5,
+      "customTags": {"systemTags": [], "userTags": [7]},
+    }));
+
+    let mut query = TransactionQuery::new(start, end);
+    assert!(query.matches(&t));
+
+    query
+      .category_ids(&[5])
+      .tag_ids(&[7])
+      .min_amount(20.0)
+      .max_amount(30.0)
+      .credit(false)
+      .duplicates(Duplicates::Exclude)
+      .description(Regex::new("(?i)coffee").unwrap());
+    assert!(query.matches(&t));
+
+    assert!(!query.clone().tag_ids(&[8]).matches(&t));
+    assert!(!query.clone().max_amount(20.0).matches(&t));
+    assert!(!query.clone().duplicates(Duplicates::Only).matches(&t));
+    assert!(!TransactionQuery::new(end, end).matches(&t));
+  }
+}
@@ -0,0 +1,393 @@
+use std::collections::HashMap;
+
+use chrono::{Datelike, NaiveDate};
+
+use crate::{
+  merchant::{self, MerchantNormalizer},
+  pc_types,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cadence {
+  Weekly,
+  Monthly,
+  Annual,
+}
+
+impl Cadence {
+  // Classifies a typical gap between occurrences.
+  fn from_days(days: i64) -> Option<Self> {
+    match days {
+      5..=9 => Some(Self::Weekly),
+      25..=35 => Some(Self::Monthly),
+      350..=380 => Some(Self::Annual),
+      _ => None,
+    }
+  }
+
+  // How many days an occurrence may be early or late.
+  fn tolerance_days(&self) -> i64 {
+    match self {
+      Self::Weekly => 2,
+      Self::Monthly => 5,
+      Self::Annual => 14,
+    }
+  }
+
+  // Steps `n` periods forward. Monthly and annual steps keep the day of the
+  // month, clamped to the length of the target month.
+  pub fn advance(&self, date: NaiveDate, n: u32) -> NaiveDate {
+    match self {
+      Self::Weekly => date + chrono::Duration::weeks(i64::from(n)),
+      Self::Monthly => add_months(date, n),
+      Self::Annual => add_months(date, n * 12),
+    }
+  }
+}
+
+fn add_months(date: NaiveDate, n: u32) -> NaiveDate {
+  let months = date.month0() + n;
+  let year = date.year() + (months / 12) as i32;
+  let month = months % 12 + 1;
+  let mut day = date.day();
+  loop {
+    if let Some(d) = NaiveDate::from_ymd_opt(year, month, day) {
+      return d;
+    }
+    day -= 1;
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PriceChange {
+  pub user_transaction_id: i64,
+  pub date: NaiveDate,
+  pub previous_amount: f64,
+  pub amount: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecurringSeries {
+  // Normalized merchant key the occurrences were grouped by.
+  pub key: String,
+  pub name: String,
+  pub cadence: Cadence,
+  pub is_credit: bool,
+  // Occurrences, oldest first. They can span several accounts.
+  pub user_transaction_ids: Vec<i64>,
+  pub user_account_ids: Vec<i64>,
+  pub first_date: NaiveDate,
+  pub last_date: NaiveDate,
+  // The amount of the latest occurrence.
+  pub expected_amount: f64,
+  // None once the series has lapsed.
+  pub next_date: Option<NaiveDate>,
+  // Expected dates that passed without an occurrence, including any since the
+  // latest one up to the point the series lapsed.
+  pub missed: Vec<NaiveDate>,
+  // Too many expected dates passed since the latest occurrence, e.g. because
+  // the subscription was cancelled.
+  pub lapsed: bool,
+  pub price_changes: Vec<PriceChange>,
+  // Transactions from the same merchant that don't fall on the cadence, e.g. a
+  // one-off purchase or a charge that came too late. They're left out of
+  // everything above.
+  pub outliers: Vec<i64>,
+}
+
+// Finds subscriptions and recurring bills (or income) by grouping
+// transactions on their merchant and looking for a weekly, monthly or annual
+// cadence. Duplicates are ignored.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecurringDetector {
+  amount_tolerance: f64,
+  min_occurrences: usize,
+  lapse_after: usize,
+  normalizer: MerchantNormalizer,
+}
+
+impl Default for RecurringDetector {
+  fn default() -> Self {
+    RecurringDetector {
+      amount_tolerance: 0.1,
+      min_occurrences: 3,
+      lapse_after: 3,
+      normalizer: MerchantNormalizer::default(),
+    }
+  }
+}
+
+impl RecurringDetector {
+  pub fn new() -> Self {
+    RecurringDetector::default()
+  }
+
+  // The fraction an amount may differ from the previous occurrence before it
+  // is reported as a price change. Defaults to 10%.
+  pub fn amount_tolerance(&mut self, value: f64) -> &mut Self {
+    self.amount_tolerance = value;
+    self
+  }
+
+  // Occurrences needed before weekly and monthly series are reported. Annual
+  // series only need two. Defaults to 3.
+  pub fn min_occurrences(&mut self, value: usize) -> &mut Self {
+    self.min_occurrences = value.max(2);
+    self
+  }
+
+  // Expected dates that may pass without an occurrence after the latest one
+  // before the series is reported as lapsed. Defaults to 3.
+  pub fn lapse_after(&mut self, value: usize) -> &mut Self {
+    self.lapse_after = value.max(1);
+    self
+  }
+
+  // Groups transactions from the same merchant into one series.
+  pub fn merchant_normalizer(&mut self, value: MerchantNormalizer) -> &mut Self {
+    self.normalizer = value;
+    self
+  }
+
+  // Detects series as of the given date, which is used to find missed
+  // occurrences and the next expected one.
+  pub fn detect(
+    &self,
+    transactions: &[pc_types::Transaction],
+    as_of: NaiveDate,
+  ) -> Vec<RecurringSeries> {
+    let mut groups: HashMap<(String, bool), Vec<&pc_types::Transaction>> = HashMap::new();
+    for t in transactions.iter().filter(|t| !t.is_duplicate) {
+      let key = self.normalizer.key(t);
+      if !key.is_empty() {
+        groups.entry((key, t.is_credit)).or_default().push(t);
+      }
+    }
+
+    let mut series: Vec<_> = groups
+      .into_iter()
+      .filter_map(|((key, is_credit), mut group)| {
+        group.sort_by_key(|t| (t.transaction_date, t.user_transaction_id));
+        self.series(key, is_credit, &group, as_of)
+      })
+      .collect();
+    series.sort_by(|a, b| a.key.cmp(&b.key).then(a.is_credit.cmp(&b.is_credit)));
+    series
+  }
+
+  fn series(
+    &self,
+    key: String,
+    is_credit: bool,
+    group: &[&pc_types::Transaction],
+    as_of: NaiveDate,
+  ) -> Option<RecurringSeries> {
+    if group.len() < 2 {
+      return None;
+    }
+
+    let mut gaps: Vec<i64> = group
+      .windows(2)
+      .map(|w| (w[1].transaction_date - w[0].transaction_date).num_days())
+      .collect();
+    gaps.sort_unstable();
+    let cadence = Cadence::from_days(gaps[gaps.len() / 2])?;
+    if cadence != Cadence::Annual && group.len() < self.min_occurrences {
+      return None;
+    }
+
+    // Expected dates are counted from the first occurrence so that clamping the
+    // day of the month doesn't drift. Each later occurrence has to be close to
+    // an expected date after the previous one; periods skipped on the way are
+    // missed occurrences.
+    let anchor = group[0].transaction_date;
+    let tolerance = chrono::Duration::days(cadence.tolerance_days());
+    let mut occurrences = vec![group[0]];
+    let mut outliers = vec![];
+    let mut missed = vec![];
+    let mut periods = 0;
+    for t in &group[1..] {
+      let date = t.transaction_date;
+      let found = (periods + 1..)
+        .take_while(|n| cadence.advance(anchor, *n) <= date + tolerance)
+        .find(|n| cadence.advance(anchor, *n) >= date - tolerance);
+      match found {
+        Some(n) => {
+          missed.extend((periods + 1..n).map(|n| cadence.advance(anchor, n)));
+          occurrences.push(t);
+          periods = n;
+        },
+        None => outliers.push(t.user_transaction_id),
+      }
+    }
+    if occurrences.len() < 2
+      || (cadence != Cadence::Annual && occurrences.len() < self.min_occurrences)
+    {
+      return None;
+    }
+
+    let mut price_changes = vec![];
+    for w in occurrences.windows(2) {
+      let (prev, next) = (w[0].amount, w[1].amount);
+      if (next - prev).abs() > self.amount_tolerance * prev.abs() {
+        price_changes.push(PriceChange {
+          user_transaction_id: w[1].user_transaction_id,
+          date: w[1].transaction_date,
+          previous_amount: prev,
+          amount: next,
+        });
+      }
+    }
+    // Amounts that change all the time are regular shopping, not a bill.
+    if price_changes.len() * 2 > occurrences.len() - 1 {
+      return None;
+    }
+
+    let last = occurrences[occurrences.len() - 1];
+    let mut next_date = cadence.advance(anchor, periods + 1);
+    let mut overdue = 0;
+    while next_date + tolerance < as_of && overdue < self.lapse_after {
+      missed.push(next_date);
+      overdue += 1;
+      periods += 1;
+      next_date = cadence.advance(anchor, periods + 1);
+    }
+    let lapsed = next_date + tolerance < as_of;
+
+    let mut user_account_ids: Vec<_> = occurrences.iter().map(|t| t.user_account_id).collect();
+    user_account_ids.sort_unstable();
+    user_account_ids.dedup();
+
+    Some(RecurringSeries {
+      key,
+      name: merchant::merchant_name(last).to_string(),
+      cadence,
+      is_credit,
+      user_transaction_ids: occurrences.iter().map(|t| t.user_transaction_id).collect(),
+      user_account_ids,
+      first_date: anchor,
+      last_date: last.transaction_date,
+      expected_amount: last.amount,
+      next_date: if lapsed { None } else { Some(next_date) },
+      missed,
+      lapsed,
+      price_changes,
+      outliers,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use pretty_assertions::assert_eq;
+  use serde_json::json;
+
+  use super::*;
+  use crate::test_util::transaction;
+
+  #[test]
+  fn test_detect() {
+    let streaming = |id: i64, date: &str, amount: f64| {
+      transaction(json!({
+        "userTransactionId": id,
+        "description": format!("NETFLIX.COM {}", id),
+        "merchant": "Netflix",
+        "transactionDate": date,
+        "amount": amount,
+      }))
+    };
+    let transactions = vec![
+      streaming(1, "2021-01-31", 13.99),
+      streaming(2, "2021-02-28", 13.99),
+      // March is missing.
+      streaming(3, "2021-04-30", 13.99),
+      streaming(4, "2021-05-31", 15.49),
+      streaming(5, "2021-06-30", 15.49),
+      // A one-off purchase from the same merchant.
+      streaming(9, "2021-05-10", 6.99),
+      transaction(json!({"userTransactionId": 6, "transactionDate": "2021-06-01", "amount": 4.0})),
+      transaction(json!({"userTransactionId": 7, "transactionDate": "2021-06-08", "amount": 9.0})),
+      transaction(json!({"userTransactionId": 8, "transactionDate": "2021-06-15", "amount": 2.5})),
+    ];
+
+    let series = RecurringDetector::new().detect(&transactions, NaiveDate::from_ymd(2021, 8, 10));
+    assert_eq!(series.len(), 1);
+
+    let s = &series[0];
+    assert_eq!(s.key, "NETFLIX");
+    assert_eq!(s.name, "Netflix");
+    assert_eq!(s.cadence, Cadence::Monthly);
+    assert_eq!(s.expected_amount, 15.49);
+    assert_eq!(s.user_transaction_ids, vec![1, 2, 3, 4, 5]);
+    assert_eq!(
+      s.missed,
+      vec![
+        NaiveDate::from_ymd(2021, 3, 31),
+        NaiveDate::from_ymd(2021, 7, 31),
+      ]
+    );
+    assert_eq!(s.next_date, Some(NaiveDate::from_ymd(2021, 8, 31)));
+    assert!(!s.lapsed);
+    assert_eq!(s.price_changes.len(), 1);
+    assert_eq!(s.price_changes[0].user_transaction_id, 4);
+    assert_eq!(s.outliers, vec![9]);
+  }
+
+  #[test]
+  fn test_detect_late() {
+    let transactions: Vec<_> = ["01-01", "02-01", "03-09", "04-01", "05-01", "06-01"]
+      .iter()
+      .enumerate()
+      .map(|(i, date)| {
+        transaction(json!({
+          "userTransactionId": i + 1,
+          "description": "CITY GYM",
+          "transactionDate": format!("2021-{}", date),
+          "amount": 40.0,
+        }))
+      })
+      .collect();
+
+    let series = RecurringDetector::new().detect(&transactions, NaiveDate::from_ymd(2021, 6, 20));
+    assert_eq!(series.len(), 1);
+
+    // Eight days late is too late for a monthly charge, but the series stays.
+    let s = &series[0];
+    assert_eq!(s.user_transaction_ids, vec![1, 2, 4, 5, 6]);
+    assert_eq!(s.outliers, vec![3]);
+    assert_eq!(s.missed, vec![NaiveDate::from_ymd(2021, 3, 1)]);
+    assert_eq!(s.next_date, Some(NaiveDate::from_ymd(2021, 7, 1)));
+  }
+
+  #[test]
+  fn test_detect_lapsed() {
+    let transactions: Vec<_> = (0..8)
+      .map(|week| {
+        let date = NaiveDate::from_ymd(2018, 3, 5) + chrono::Duration::weeks(week);
+        transaction(json!({
+          "userTransactionId": week + 1,
+          "description": "MEAL KIT CO",
+          "transactionDate": date,
+          "amount": 60.0,
+        }))
+      })
+      .collect();
+
+    // Cancelled three years before.
+    let series = RecurringDetector::new().detect(&transactions, NaiveDate::from_ymd(2021, 6, 1));
+    assert_eq!(series.len(), 1);
+
+    let s = &series[0];
+    assert_eq!(s.cadence, Cadence::Weekly);
+    assert!(s.lapsed);
+    assert_eq!(s.next_date, None);
+    assert_eq!(
+      s.missed,
+      vec![
+        NaiveDate::from_ymd(2018, 4, 30),
+        NaiveDate::from_ymd(2018, 5, 7),
+        NaiveDate::from_ymd(2018, 5, 14),
+      ]
+    );
+  }
+}
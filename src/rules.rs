@@ -0,0 +1,318 @@
+use std::collections::BTreeMap;
+
+use regex::Regex;
+
+use crate::{
+  merchant::{self, MerchantNormalizer},
+  pc_types,
+  query::TransactionQuery,
+  UpdateUserTransactionsArgs,
+};
+
+// Matches transactions on any combination of conditions and sets their
+// category, description or tags. A rule without conditions matches everything.
+#[derive(Debug, Clone)]
+pub struct Rule {
+  name: String,
+  // The conditions a rule shares with Client::query_transactions().
+  query: TransactionQuery,
+  original_description: Option<Regex>,
+  merchant: Option<Regex>,
+  merchant_key: Option<String>,
+  set_category_id: Option<i64>,
+  set_description: Option<String>,
+  set_tags: Option<Vec<i64>>,
+}
+
+impl Rule {
+  pub fn new(name: &str) -> Self {
+    Rule {
+      name: name.to_string(),
+      query: TransactionQuery::new(chrono::naive::MIN_DATE, chrono::naive::MAX_DATE),
+      original_description: None,
+      merchant: None,
+      merchant_key: None,
+      set_category_id: None,
+      set_description: None,
+      set_tags: None,
+    }
+  }
+
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+
+  // Matched against both the description and the original description.
+  pub fn description(&mut self, value: Regex) -> &mut Self {
+    self.query.description(value);
+    self
+  }
+
+  pub fn original_description(&mut self, value: Regex) -> &mut Self {
+    self.original_description = Some(value);
+    self
+  }
+
+  // Matched against the merchant, falling back to the simple description and
+  // then the description when the server didn't give one.
+  pub fn merchant(&mut self, value: Regex) -> &mut Self {
+    self.merchant = Some(value);
+    self
+  }
+
+  // Matches transactions whose normalized merchant key is the same as that of
+  // `value`, e.g. "Coffee Shop" matches "SQ *COFFEE SHOP 1234 SEATTLE WA".
+  pub fn merchant_key(&mut self, value: &str) -> &mut Self {
+    self.merchant_key = Some(value.to_string());
+    self
+  }
+
+  pub fn account_ids(&mut self, value: &[i64]) -> &mut Self {
+    self.query.account_ids(value);
+    self
+  }
+
+  // Inclusive bounds on the (unsigned) transaction amount.
+  pub fn min_amount(&mut self, value: f64) -> &mut Self {
+    self.query.min_amount(value);
+    self
+  }
+
+  pub fn max_amount(&mut self, value: f64) -> &mut Self {
+    self.query.max_amount(value);
+    self
+  }
+
+  pub fn transaction_types(&mut self, value: &[pc_types::TransactionType]) -> &mut Self {
+    self.query.transaction_types(value);
+    self
+  }
+
+  pub fn set_category_id(&mut self, value: i64) -> &mut Self {
+    self.set_category_id = Some(value);
+    self
+  }
+
+  pub fn set_description(&mut self, value: &str) -> &mut Self {
+    self.set_description = Some(value.to_string());
+    self
+  }
+
+  // Replaces the user tags.
+  pub fn set_tags(&mut self, value: &[i64]) -> &mut Self {
+    let mut tags = value.to_vec();
+    tags.sort_unstable();
+    tags.dedup();
+    self.set_tags = Some(tags);
+    self
+  }
+
+  // Compares merchant_key() with MerchantNormalizer::default(), so a rule
+  // can match differently here than in Rules::dry_run() when Rules has its own
+  // aliases. Use matches_with() to pass the same normalizer.
+  pub fn matches(&self, t: &pc_types::Transaction) -> bool {
+    self.matches_with(t, &merchant::DEFAULT_NORMALIZER)
+  }
+
+  // Like matches(), with the normalizer used by merchant_key().
+  pub fn matches_with(&self, t: &pc_types::Transaction, normalizer: &MerchantNormalizer) -> bool {
+    let re_match = |re: &Option<Regex>, s: &str| re.as_ref().map(|re| re.is_match(s));
+
+    if !self.query.matches(t)
+      || re_match(&self.original_description, &t.original_description) == Some(false)
+      || re_match(&self.merchant, merchant::merchant_name(t)) == Some(false)
+    {
+      return false;
+    }
+
+    match &self.merchant_key {
+      Some(key) => normalizer.normalize(key) == normalizer.key(t),
+      None => true,
+    }
+  }
+
+  // The part of this rule's actions that would actually change `t`.
+  fn change(&self, t: &pc_types::Transaction) -> Option<Change> {
+    let mut current_tags: Vec<i64> = t
+      .custom_tags
+      .as_ref()
+      .map(|c| c.user_tags.clone())
+      .unwrap_or_default();
+    current_tags.sort_unstable();
+
+    let change = Change {
+      user_transaction_id: t.user_transaction_id,
+      rule: self.name.clone(),
+      category_id: self.set_category_id.filter(|id| *id != t.category_id),
+      description: self.set_description.clone().filter(|d| *d != t.description),
+      tags: self.set_tags.clone().filter(|tags| *tags != current_tags),
+    };
+
+    if change.category_id.is_none() && change.description.is_none() && change.tags.is_none() {
+      return None;
+    }
+
+    Some(change)
+  }
+}
+
+// What a rule would change on one transaction. Fields that already have the
+// wanted value are None.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+  pub user_transaction_id: i64,
+  pub rule: String,
+  pub category_id: Option<i64>,
+  pub description: Option<String>,
+  pub tags: Option<Vec<i64>>,
+}
+
+// An ordered list of rules. For each transaction only the first matching rule
+// applies. Transactions the server doesn't allow editing are skipped.
+#[derive(Debug, Clone, Default)]
+pub struct Rules {
+  rules: Vec<Rule>,
+  normalizer: MerchantNormalizer,
+}
+
+impl Rules {
+  pub fn new() -> Self {
+    Rules::default()
+  }
+
+  pub fn rule(&mut self, rule: Rule) -> &mut Self {
+    self.rules.push(rule);
+    self
+  }
+
+  pub fn rules(&self) -> &[Rule] {
+    &self.rules
+  }
+
+  // Compares Rule::merchant_key() conditions in dry_run(), so aliases added
+  // here apply to every rule.
+  pub fn merchant_normalizer(&mut self, value: MerchantNormalizer) -> &mut Self {
+    self.normalizer = value;
+    self
+  }
+
+  // Works out the changes without sending anything.
+  pub fn dry_run(&self, transactions: &[pc_types::Transaction]) -> Vec<Change> {
+    transactions
+      .iter()
+      .filter(|t| t.is_editable)
+      .filter_map(|t| {
+        self
+          .rules
+          .iter()
+          .find(|r| r.matches_with(t, &self.normalizer))?
+          .change(t)
+      })
+      .collect()
+  }
+}
+
+// Groups changes that set the same values into one update each, which is what
+// Client::apply_rules() sends.
+pub fn batch_changes(changes: &[Change]) -> Vec<UpdateUserTransactionsArgs> {
+  let mut batches: BTreeMap<_, Vec<i64>> = BTreeMap::new();
+  for c in changes {
+    batches
+      .entry((c.category_id, c.description.clone(), c.tags.clone()))
+      .or_default()
+      .push(c.user_transaction_id);
+  }
+
+  batches
+    .into_iter()
+    .map(
+      |((category_id, description, tags), transaction_ids)| UpdateUserTransactionsArgs {
+        transaction_ids,
+        category_id,
+        description,
+        tags,
+        duplicate: None,
+      },
+    )
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use pretty_assertions::assert_eq;
+  use serde_json::json;
+
+  use super::*;
+  use crate::test_util::transaction;
+
+  #[test]
+  fn test_rules() {
+    let mut coffee = Rule::new("coffee");
+    coffee
+      .merchant_key("Coffee Shop")
+      .max_amount(20.0)
+      .set_category_id(7);
+    let mut catch_all = Rule::new("catch all");
+    catch_all.set_tags(&[3]);
+
+    let mut rules = Rules::new();
+    rules.rule(coffee).rule(catch_all);
+
+    let transactions = vec![
+      transaction(json!({"userTransactionId": 1})),
+      transaction(json!({
+        "userTransactionId": 2,
+        "amount": 4.5,
+        "description": "SQ *COFFEE SHOP 1234 SEATTLE WA",
+      })),
+      // Already categorized.
+      transaction(json!({"userTransactionId": 3, "categoryId": 7})),
+      // Too expensive for the coffee rule, so the catch all applies.
+      transaction(json!({"userTransactionId": 4, "amount": 50.0})),
+      transaction(json!({
+        "userTransactionId": 5,
+        "amount": 50.0,
+        "customTags": {"systemTags": [], "userTags": [3]},
+      })),
+      transaction(json!({"userTransactionId": 6, "isEditable": false})),
+    ];
+
+    let changes = rules.dry_run(&transactions);
+    assert_eq!(
+      changes
+        .iter()
+        .map(|c| (c.user_transaction_id, c.rule.as_str()))
+        .collect::<Vec<_>>(),
+      vec![(1, "coffee"), (2, "coffee"), (4, "catch all")]
+    );
+
+    let batches = batch_changes(&changes);
+    assert_eq!(batches.len(), 2);
+    assert_eq!(batches[0].transaction_ids, vec![4]);
+    assert_eq!(batches[0].tags, Some(vec![3]));
+    assert_eq!(batches[1].transaction_ids, vec![1, 2]);
+    assert_eq!(batches[1].category_id, Some(7));
+
+    let mut visa = Rule::new("visa");
+    visa
+      .account_ids(&[2])
+      .description(Regex::new("^COFFEE").unwrap());
+    assert!(!visa.matches(&transactions[0]));
+    // The description is also matched against the original description.
+    assert!(visa.matches(&transaction(json!({"userAccountId": 2}))));
+
+    let mut normalizer = MerchantNormalizer::new();
+    normalizer.alias("Blue Bottle", "Coffee Shop");
+    let blue_bottle = transaction(json!({
+      "userTransactionId": 7,
+      "description": "BLUE BOTTLE OAKLAND CA",
+      "merchant": "Blue Bottle",
+    }));
+    assert!(!rules.rules()[0].matches(&blue_bottle));
+    assert!(rules.rules()[0].matches_with(&blue_bottle, &normalizer));
+
+    rules.merchant_normalizer(normalizer);
+    let changes = rules.dry_run(&[blue_bottle]);
+    assert_eq!(changes[0].rule, "coffee");
+  }
+}
@@ -2,7 +2,10 @@ use serde::{
   de::{Deserializer, Error, IntoDeserializer, Visitor},
   Deserialize,
 };
-use std::fmt::{Formatter, Result as FResult};
+use std::{
+  collections::HashMap,
+  fmt::{Formatter, Result as FResult},
+};
 
 struct F64;
 
@@ -17,15 +20,39 @@ impl<'de> Visitor<'de> for F64 {
     Ok(id)
   }
 
+  fn visit_i64<E: Error>(self, id: i64) -> Result<Self::Value, E> {
+    Ok(id as f64)
+  }
+
+  fn visit_u64<E: Error>(self, id: u64) -> Result<Self::Value, E> {
+    Ok(id as f64)
+  }
+
   fn visit_str<E: Error>(self, s: &str) -> Result<Self::Value, E> {
     s.parse().map_err(Error::custom)
   }
 }
 
+// Anything that isn't a usable number (null, "", "NaN", ...) becomes None.
 pub(crate) fn deserialize_f64_option<'de, D: Deserializer<'de>>(
   deserializer: D,
 ) -> Result<Option<f64>, D::Error> {
-  deserializer.deserialize_any(F64).map(Some).or(Ok(None))
+  Ok(
+    deserializer
+      .deserialize_any(F64)
+      .ok()
+      .filter(|v| !v.is_nan()),
+  )
+}
+
+pub(crate) fn deserialize_f64_option_map<'de, D: Deserializer<'de>>(
+  deserializer: D,
+) -> Result<HashMap<String, Option<f64>>, D::Error> {
+  #[derive(Deserialize)]
+  struct MaybeF64(#[serde(deserialize_with = "deserialize_f64_option")] Option<f64>);
+
+  let map: HashMap<String, MaybeF64> = Deserialize::deserialize(deserializer)?;
+  Ok(map.into_iter().map(|(k, MaybeF64(v))| (k, v)).collect())
 }
 
 // pub(crate) fn deserialize_milli_ts_option<'de, D: Deserializer<'de>>(
@@ -69,6 +96,60 @@ where
       } else {
         Err(serde::de::Error::custom("Unexpected string"))
       }
-    }
+    },
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use pretty_assertions::assert_eq;
+  use serde_json::json;
+
+  use crate::pc_types::{AccountPerformance, History, UserAccountId};
+
+  #[test]
+  fn test_deserialize_f64_option_map() {
+    let history: History = serde_json::from_value(json!({
+      "date": "2021-01-01",
+      "aggregateBalance": 100.0,
+      "balances": {
+        "1": "NaN",
+        "2": "",
+        "3": null,
+        "4": 5,
+        "5": 2.25,
+        "6": "1.5",
+      },
+    }))
+    .unwrap();
+
+    let balances: Vec<_> = (1..=6)
+      .map(|id| history.balance(UserAccountId(id)))
+      .collect();
+    assert_eq!(
+      balances,
+      vec![None, None, None, Some(5.0), Some(2.25), Some(1.5)]
+    );
+    assert_eq!(history.account_balances().len(), 3);
+  }
+
+  #[test]
+  fn test_deserialize_f64_option() {
+    let balance = |value: serde_json::Value| {
+      let account = json!({"userAccountId": 1, "currentBalance": value});
+      serde_json::from_value::<AccountPerformance>(account)
+        .unwrap()
+        .current_balance
+    };
+
+    assert_eq!(balance(json!("NaN")), None);
+    assert_eq!(balance(json!("")), None);
+    assert_eq!(balance(json!(null)), None);
+    assert_eq!(balance(json!(42)), Some(42.0));
+    assert_eq!(balance(json!(-7.5)), Some(-7.5));
+    assert_eq!(balance(json!("12.25")), Some(12.25));
+
+    let missing: AccountPerformance = serde_json::from_value(json!({"userAccountId": 1})).unwrap();
+    assert_eq!(missing.current_balance, None);
   }
 }
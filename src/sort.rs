@@ -1,6 +1,32 @@
-use std::cmp::Ordering;
+use std::{cmp::Ordering, str::FromStr};
 
-use crate::pc_types;
+use crate::{pc_types, Error};
+
+// Orders floats without panicking. NaN sorts after every number.
+fn cmp_f64(a: f64, b: f64) -> Ordering {
+  a.partial_cmp(&b)
+    .unwrap_or_else(|| a.is_nan().cmp(&b.is_nan()))
+}
+
+// Missing values sort before any number.
+fn cmp_f64_option(a: Option<f64>, b: Option<f64>) -> Ordering {
+  match (a, b) {
+    (Some(a), Some(b)) => cmp_f64(a, b),
+    (a, b) => a.is_some().cmp(&b.is_some()),
+  }
+}
+
+fn cmp_uppercase(a: &str, b: &str) -> Ordering {
+  a.to_uppercase().cmp(&b.to_uppercase())
+}
+
+fn signed_amount(t: &pc_types::Transaction) -> f64 {
+  if t.is_credit {
+    t.amount
+  } else {
+    -t.amount
+  }
+}
 
 fn transaction_cmp_status(a: &pc_types::Transaction, b: &pc_types::Transaction) -> Ordering {
   let i = a.status == pc_types::TransactionStatus::Pending;
@@ -15,57 +41,63 @@ fn transaction_cmp_status(a: &pc_types::Transaction, b: &pc_types::Transaction)
 }
 
 fn transaction_cmp_date(a: &pc_types::Transaction, b: &pc_types::Transaction) -> Ordering {
-  b.transaction_date.partial_cmp(&a.transaction_date).unwrap()
+  b.transaction_date.cmp(&a.transaction_date)
 }
 
 fn transaction_cmp_id(a: &pc_types::Transaction, b: &pc_types::Transaction) -> Ordering {
-  b.user_transaction_id
-    .partial_cmp(&a.user_transaction_id)
-    .unwrap()
+  b.user_transaction_id.cmp(&a.user_transaction_id)
 }
 
 fn transaction_cmp_account_name(a: &pc_types::Transaction, b: &pc_types::Transaction) -> Ordering {
-  let a = a.account_name.to_uppercase();
-  let b = b.account_name.to_uppercase();
-
-  a.partial_cmp(&b).unwrap()
+  cmp_uppercase(&a.account_name, &b.account_name)
 }
 
 fn transaction_cmp_amount(a: &pc_types::Transaction, b: &pc_types::Transaction) -> Ordering {
-  let a = if a.is_credit { a.amount } else { -a.amount };
-  let b = if b.is_credit { b.amount } else { -b.amount };
-
-  a.partial_cmp(&b).unwrap()
+  cmp_f64(signed_amount(a), signed_amount(b))
 }
 
 fn transaction_cmp_description(a: &pc_types::Transaction, b: &pc_types::Transaction) -> Ordering {
-  let a = a.description.to_uppercase();
-  let b = b.description.to_uppercase();
-
-  a.partial_cmp(&b).unwrap()
+  cmp_uppercase(&a.description, &b.description)
 }
 
 fn transaction_cmp_price(a: &pc_types::Transaction, b: &pc_types::Transaction) -> Ordering {
-  a.price.partial_cmp(&b.price).unwrap()
+  cmp_f64_option(a.price, b.price)
 }
 
 fn transaction_cmp_quantity(a: &pc_types::Transaction, b: &pc_types::Transaction) -> Ordering {
-  a.quantity.partial_cmp(&b.quantity).unwrap()
-}
-
-// TODO
-// fn transaction_cmp_tags(a: &pc_types::Transaction, b: &pc_types::Transaction) -> Ordering {
-// const n = r.a.get(e, "customTags.systemTags", [])
-//           , i = r.a.get(a, "customTags.systemTags", [])
-//           , o = r.a.get(e, "customTags.userTags", [])
-//           , d = r.a.get(a, "customTags.userTags", []);
-//         let u, h;
-//         return 1 === n.length && 1 === i.length && Object(l.isEmpty)(o) && Object(l.isEmpty)(d) && (u = n[0] - 100001,
-//         h = i[0] - 100001),
-//         n.length + o.length - (i.length + d.length) || u - h || s()(e.transactionDate, c.a) - s()(a.transactionDate, c.a)
-//}
-
-// This will sort transactions exactly the way the UI does it.
+  cmp_f64_option(a.quantity, b.quantity)
+}
+
+// The web app's tag column ordering. Transactions with fewer tags come first.
+// If both have exactly one system tag and no user tags, they're ordered by that
+// tag, otherwise by date ascending.
+fn transaction_cmp_tags(a: &pc_types::Transaction, b: &pc_types::Transaction) -> Ordering {
+  fn tags(t: &pc_types::Transaction) -> (&[i64], &[i64]) {
+    t.custom_tags
+      .as_ref()
+      .map(|c| (&c.system_tags[..], &c.user_tags[..]))
+      .unwrap_or((&[], &[]))
+  }
+  let (a_system, a_user) = tags(a);
+  let (b_system, b_user) = tags(b);
+
+  let o = (a_system.len() + a_user.len()).cmp(&(b_system.len() + b_user.len()));
+  if o != Ordering::Equal {
+    return o;
+  }
+
+  if a_system.len() == 1 && b_system.len() == 1 && a_user.is_empty() && b_user.is_empty() {
+    let o = a_system[0].cmp(&b_system[0]);
+    if o != Ordering::Equal {
+      return o;
+    }
+  }
+
+  a.transaction_date.cmp(&b.transaction_date)
+}
+
+// The default transaction order: pending first, then newest first. Tag
+// ordering is only applied when sorting by TransactionSortKey::Tags.
 pub fn transaction_cmp(a: &pc_types::Transaction, b: &pc_types::Transaction) -> Ordering {
   let comparisons = [
     transaction_cmp_status,
@@ -76,7 +108,6 @@ pub fn transaction_cmp(a: &pc_types::Transaction, b: &pc_types::Transaction) ->
     transaction_cmp_description,
     transaction_cmp_price,
     transaction_cmp_quantity,
-    // transaction_cmp_tags,
   ];
   for cmp in comparisons {
     let o = cmp(a, b);
@@ -88,27 +119,305 @@ pub fn transaction_cmp(a: &pc_types::Transaction, b: &pc_types::Transaction) ->
   Ordering::Equal
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortOrder {
+  Asc,
+  Desc,
+}
+
+impl Default for SortOrder {
+  fn default() -> Self {
+    Self::Asc
+  }
+}
+
+impl AsRef<str> for SortOrder {
+  fn as_ref(&self) -> &'static str {
+    match self {
+      Self::Asc => "asc",
+      Self::Desc => "desc",
+    }
+  }
+}
+
+// A field that items of type `Item` can be ordered by. `cmp` is always the
+// ascending order; SortSpec takes care of reversing it.
+pub trait SortKey: Copy + FromStr<Err = Error> {
+  type Item;
+
+  fn cmp(&self, a: &Self::Item, b: &Self::Item) -> Ordering;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransactionSortKey {
+  // Pending before posted.
+  Status,
+  Date,
+  Id,
+  AccountName,
+  // Credits are positive and debits negative.
+  Amount,
+  Description,
+  Price,
+  Quantity,
+  Category,
+  // Same ordering as the UI's tag column.
+  Tags,
+}
+
+impl AsRef<str> for TransactionSortKey {
+  fn as_ref(&self) -> &'static str {
+    match self {
+      Self::Status => "status",
+      Self::Date => "date",
+      Self::Id => "id",
+      Self::AccountName => "account_name",
+      Self::Amount => "amount",
+      Self::Description => "description",
+      Self::Price => "price",
+      Self::Quantity => "quantity",
+      Self::Category => "category",
+      Self::Tags => "tags",
+    }
+  }
+}
+
+impl FromStr for TransactionSortKey {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Ok(match s {
+      "status" => Self::Status,
+      "date" => Self::Date,
+      "id" => Self::Id,
+      "account_name" | "account" => Self::AccountName,
+      "amount" => Self::Amount,
+      "description" => Self::Description,
+      "price" => Self::Price,
+      "quantity" => Self::Quantity,
+      "category" => Self::Category,
+      "tags" => Self::Tags,
+      _ => return Err(Error::InvalidSortSpec(s.to_string())),
+    })
+  }
+}
+
+impl SortKey for TransactionSortKey {
+  type Item = pc_types::Transaction;
+
+  fn cmp(&self, a: &Self::Item, b: &Self::Item) -> Ordering {
+    match self {
+      Self::Status => transaction_cmp_status(a, b),
+      // The UI comparators for these are newest/highest first.
+      Self::Date => transaction_cmp_date(b, a),
+      Self::Id => transaction_cmp_id(b, a),
+      Self::AccountName => transaction_cmp_account_name(a, b),
+      Self::Amount => transaction_cmp_amount(a, b),
+      Self::Description => transaction_cmp_description(a, b),
+      Self::Price => transaction_cmp_price(a, b),
+      Self::Quantity => transaction_cmp_quantity(a, b),
+      Self::Category => a.category_id.cmp(&b.category_id),
+      Self::Tags => transaction_cmp_tags(a, b),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HoldingSortKey {
+  Ticker,
+  Description,
+  AccountName,
+  Quantity,
+  Price,
+  Value,
+  Change,
+  OneDayValueChange,
+  OneDayPercentChange,
+  HoldingPercentage,
+  CostBasis,
+  UnrealizedGain,
+  // The server's precomputed rank for each column.
+  ValueSortIndex,
+  ChangeSortIndex,
+  OneDayValueChangeSortIndex,
+  OneDayPercentChangeSortIndex,
+}
+
+impl AsRef<str> for HoldingSortKey {
+  fn as_ref(&self) -> &'static str {
+    match self {
+      Self::Ticker => "ticker",
+      Self::Description => "description",
+      Self::AccountName => "account_name",
+      Self::Quantity => "quantity",
+      Self::Price => "price",
+      Self::Value => "value",
+      Self::Change => "change",
+      Self::OneDayValueChange => "one_day_value_change",
+      Self::OneDayPercentChange => "one_day_percent_change",
+      Self::HoldingPercentage => "holding_percentage",
+      Self::CostBasis => "cost_basis",
+      Self::UnrealizedGain => "unrealized_gain",
+      Self::ValueSortIndex => "value_sort_index",
+      Self::ChangeSortIndex => "change_sort_index",
+      Self::OneDayValueChangeSortIndex => "one_day_value_change_sort_index",
+      Self::OneDayPercentChangeSortIndex => "one_day_percent_change_sort_index",
+    }
+  }
+}
+
+impl FromStr for HoldingSortKey {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Ok(match s {
+      "ticker" => Self::Ticker,
+      "description" => Self::Description,
+      "account_name" | "account" => Self::AccountName,
+      "quantity" => Self::Quantity,
+      "price" => Self::Price,
+      "value" => Self::Value,
+      "change" => Self::Change,
+      "one_day_value_change" => Self::OneDayValueChange,
+      "one_day_percent_change" => Self::OneDayPercentChange,
+      "holding_percentage" => Self::HoldingPercentage,
+      "cost_basis" => Self::CostBasis,
+      "unrealized_gain" => Self::UnrealizedGain,
+      "value_sort_index" => Self::ValueSortIndex,
+      "change_sort_index" => Self::ChangeSortIndex,
+      "one_day_value_change_sort_index" => Self::OneDayValueChangeSortIndex,
+      "one_day_percent_change_sort_index" => Self::OneDayPercentChangeSortIndex,
+      _ => return Err(Error::InvalidSortSpec(s.to_string())),
+    })
+  }
+}
+
+impl SortKey for HoldingSortKey {
+  type Item = pc_types::Holding;
+
+  fn cmp(&self, a: &Self::Item, b: &Self::Item) -> Ordering {
+    let text = |v: &Option<String>| v.as_deref().unwrap_or_default().to_uppercase();
+    match self {
+      Self::Ticker => text(&a.ticker).cmp(&text(&b.ticker)),
+      Self::Description => text(&a.description).cmp(&text(&b.description)),
+      Self::AccountName => text(&a.account_name).cmp(&text(&b.account_name)),
+      Self::Quantity => cmp_f64(a.quantity, b.quantity),
+      Self::Price => cmp_f64(a.price, b.price),
+      Self::Value => cmp_f64(a.value, b.value),
+      Self::Change => cmp_f64(a.change, b.change),
+      Self::OneDayValueChange => cmp_f64(a.one_day_value_change, b.one_day_value_change),
+      Self::OneDayPercentChange => cmp_f64(a.one_day_percent_change, b.one_day_percent_change),
+      Self::HoldingPercentage => cmp_f64(a.holding_percentage, b.holding_percentage),
+      Self::CostBasis => cmp_f64_option(a.cost_basis, b.cost_basis),
+      Self::UnrealizedGain => cmp_f64_option(a.unrealized_gain(), b.unrealized_gain()),
+      Self::ValueSortIndex => a.value_sort_index.cmp(&b.value_sort_index),
+      Self::ChangeSortIndex => a.change_sort_index.cmp(&b.change_sort_index),
+      Self::OneDayValueChangeSortIndex => a
+        .one_day_value_change_sort_index
+        .cmp(&b.one_day_value_change_sort_index),
+      Self::OneDayPercentChangeSortIndex => a
+        .one_day_percent_change_sort_index
+        .cmp(&b.one_day_percent_change_sort_index),
+    }
+  }
+}
+
+// An ordered list of keys, each ascending or descending. Later keys only break
+// ties left by earlier ones. Parses from strings like "date desc, amount asc";
+// the order defaults to ascending when omitted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortSpec<K> {
+  keys: Vec<(K, SortOrder)>,
+}
+
+pub type TransactionSortSpec = SortSpec<TransactionSortKey>;
+pub type HoldingSortSpec = SortSpec<HoldingSortKey>;
+
+impl<K: SortKey> SortSpec<K> {
+  pub fn new() -> Self {
+    SortSpec { keys: Vec::new() }
+  }
+
+  pub fn key(&mut self, key: K, order: SortOrder) -> &mut Self {
+    self.keys.push((key, order));
+    self
+  }
+
+  pub fn keys(&self) -> &[(K, SortOrder)] {
+    &self.keys
+  }
+
+  pub fn compare(&self, a: &K::Item, b: &K::Item) -> Ordering {
+    for (key, order) in &self.keys {
+      let o = match order {
+        SortOrder::Asc => key.cmp(a, b),
+        SortOrder::Desc => key.cmp(b, a),
+      };
+      if o != Ordering::Equal {
+        return o;
+      }
+    }
+
+    Ordering::Equal
+  }
+
+  // Stable, so items equal under every key keep their relative order.
+  pub fn sort(&self, items: &mut [K::Item]) {
+    items.sort_by(|a, b| self.compare(a, b));
+  }
+}
+
+impl<K: SortKey> Default for SortSpec<K> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl<K: SortKey> FromStr for SortSpec<K> {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let mut spec = Self::new();
+    for part in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+      let mut words = part.split_whitespace();
+      let key = words.next().unwrap_or_default().to_lowercase().parse()?;
+      let order = match words.next().map(str::to_lowercase).as_deref() {
+        None | Some("asc") => SortOrder::Asc,
+        Some("desc") => SortOrder::Desc,
+        Some(_) => return Err(Error::InvalidSortSpec(part.to_string())),
+      };
+      if words.next().is_some() {
+        return Err(Error::InvalidSortSpec(part.to_string()));
+      }
+      spec.key(key, order);
+    }
+
+    if spec.keys.is_empty() {
+      return Err(Error::InvalidSortSpec(s.to_string()));
+    }
+
+    Ok(spec)
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use pretty_assertions::assert_eq;
+  use serde_json::json;
   use std::{fs::File, io::BufReader};
 
   use super::*;
+  use crate::test_util::transaction;
 
   #[test]
   fn test_transaction_compare() {
-    let file_input = match File::open("./testdata/sort_test_input.json") {
-      Ok(it) => it,
-      _ => return,
-    };
+    // The fixtures are hand written rather than captured from the web app.
+    let file_input = File::open("./testdata/sort_test_input.json").unwrap();
     let reader_input = BufReader::new(file_input);
     let mut transactions_input: Vec<pc_types::Transaction> =
       serde_json::from_reader(reader_input).unwrap();
 
-    let file_output = match File::open("./testdata/sort_test_output.json") {
-      Ok(it) => it,
-      _ => return,
-    };
+    let file_output = File::open("./testdata/sort_test_output.json").unwrap();
     let reader_output = BufReader::new(file_output);
     let transactions_output: Vec<pc_types::Transaction> =
       serde_json::from_reader(reader_output).unwrap();
@@ -119,8 +428,15 @@ mod tests {
       v.iter()
         .map(|v| {
           format!(
-            "{} - {} - {} - {}",
-            v.transaction_date, v.account_name, v.description, v.amount
+            "{} - {} - {} - {} - {} - {:?}",
+            v.user_transaction_id,
+            v.transaction_date,
+            v.account_name,
+            v.description,
+            v.amount,
+            v.custom_tags
+              .as_ref()
+              .map(|c| (&c.system_tags, &c.user_tags))
           )
         })
         .collect()
@@ -134,4 +450,59 @@ mod tests {
 
     assert_eq!(v_input, v_output);
   }
+
+  #[test]
+  fn test_sort_spec() {
+    let spec: TransactionSortSpec = "date desc, Amount".parse().unwrap();
+    assert_eq!(
+      spec.keys(),
+      &[
+        (TransactionSortKey::Date, SortOrder::Desc),
+        (TransactionSortKey::Amount, SortOrder::Asc),
+      ]
+    );
+    assert!("date sideways".parse::<TransactionSortSpec>().is_err());
+    assert!("nope".parse::<TransactionSortSpec>().is_err());
+    assert!(" , ".parse::<HoldingSortSpec>().is_err());
+
+    let mut transactions = vec![
+      transaction(json!({"amount": 5.0})),
+      transaction(json!({"amount": 7.0})),
+      transaction(json!({"amount": 3.0})),
+    ];
+    transactions[0].amount = f64::NAN;
+    spec.sort(&mut transactions);
+
+    let amounts: Vec<_> = transactions.iter().map(|t| t.amount).collect();
+    assert_eq!(amounts[..2], [7.0, 3.0]);
+    assert!(amounts[2].is_nan());
+  }
+
+  #[test]
+  fn test_tags_sort_key() {
+    let tagged = |id: i64, system: &[i64], user: &[i64]| {
+      transaction(json!({
+        "userTransactionId": id,
+        "customTags": {"systemTags": system, "userTags": user},
+      }))
+    };
+    let mut transactions = vec![
+      tagged(1, &[], &[]),
+      tagged(2, &[100003], &[]),
+      tagged(3, &[100001], &[]),
+      tagged(4, &[100002], &[7]),
+      transaction(json!({"userTransactionId": 5, "transactionDate": "2020-12-31"})),
+    ];
+
+    // The default order doesn't look at tags.
+    transactions.sort_by(transaction_cmp);
+    let ids = |v: &[pc_types::Transaction]| -> Vec<i64> {
+      v.iter().map(|t| t.user_transaction_id).collect()
+    };
+    assert_eq!(ids(&transactions), vec![4, 3, 2, 1, 5]);
+
+    let spec: TransactionSortSpec = "tags".parse().unwrap();
+    spec.sort(&mut transactions);
+    assert_eq!(ids(&transactions), vec![5, 1, 3, 2, 4]);
+  }
 }
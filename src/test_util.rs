@@ -0,0 +1,113 @@
+use crate::pc_types;
+
+fn merge(mut value: serde_json::Value, fields: serde_json::Value) -> serde_json::Value {
+  if let (Some(value), Some(fields)) = (value.as_object_mut(), fields.as_object()) {
+    for (k, v) in fields {
+      value.insert(k.clone(), v.clone());
+    }
+  }
+
+  value
+}
+
+// Builds a posted debit transaction, with `fields` overriding the defaults.
+pub(crate) fn transaction(fields: serde_json::Value) -> pc_types::Transaction {
+  let value = serde_json::json!({
+    "isInterest": false,
+    "accountName": "Checking",
+    "description": "Coffee Shop",
+    "isCredit": false,
+    "isEditable": true,
+    "isCashOut": true,
+    "merchantId": "",
+    "userTransactionId": 1,
+    "currency": "USD",
+    "isDuplicate": false,
+    "originalDescription": "COFFEE SHOP",
+    "isSpending": true,
+    "amount": 10.0,
+    "transactionTypeId": 0,
+    "isIncome": false,
+    "includeInCashManager": true,
+    "isNew": false,
+    "isCashIn": false,
+    "transactionDate": "2021-01-01",
+    "transactionType": "Debit",
+    "accountId": "1",
+    "isCost": false,
+    "userAccountId": 1,
+    "hasViewed": true,
+    "categoryId": 1,
+    "status": "posted",
+  });
+
+  serde_json::from_value(merge(value, fields)).unwrap()
+}
+
+// Builds a checking account asset, with `fields` overriding the defaults.
+pub(crate) fn account(fields: serde_json::Value) -> pc_types::Account {
+  let value = serde_json::json!({
+    "isOnUs": false,
+    "nextAction": {"prompts": []},
+    "isCrypto": false,
+    "isPartner": false,
+    "isExcludeFromHousehold": false,
+    "isAsset": true,
+    "aggregating": false,
+    "balance": 100.0,
+    "isAccountUsedInFunding": false,
+    "isOnUs401K": false,
+    "userSiteId": 1,
+    "is365DayTransactionEligible": false,
+    "isManual": false,
+    "accountType": "Checking",
+    "paymentFromStatus": false,
+    "isRefetchTransactionEligible": false,
+    "accountId": "1",
+    "isManualPortfolio": false,
+    "userAccountId": 1,
+    "name": "Checking",
+    "firmName": "Bank",
+    "accountTypeGroup": "BANK",
+    "paymentToStatus": false,
+    "isOnUsBank": false,
+    "isPaymentToCapable": false,
+    "currency": "USD",
+    "isLiability": false,
+    "isEsog": false,
+    "isPaymentFromCapable": false,
+    "siteId": 1,
+    "originalFirmName": "Bank",
+  });
+
+  serde_json::from_value(merge(value, fields)).unwrap()
+}
+
+// Builds a fund holding, with `fields` overriding the defaults.
+pub(crate) fn holding(fields: serde_json::Value) -> pc_types::Holding {
+  let value = serde_json::json!({
+    "quantity": 10.0,
+    "manualClassification": "UNCLASSIFIED",
+    "isMarketMover": false,
+    "oneDayPercentChangeSortIndex": 0,
+    "oneDayValueChange": 0.0,
+    "change": 0.0,
+    "source": "YODLEE",
+    "changeSortIndex": 0,
+    "oneDayValueChangeSortIndex": 0,
+    "marketType": 0,
+    "sourceAssetId": "1",
+    "holdingType": "Fund",
+    "price": 10.0,
+    "holdingPercentage": 100.0,
+    "userAccountId": 1,
+    "priceSource": "MARKET",
+    "valueSortIndex": 0,
+    "currency": "USD",
+    "value": 100.0,
+    "oneDayPercentChange": 0.0,
+    "ticker": "VTI",
+  });
+
+  serde_json::from_value(merge(value, fields)).unwrap()
+}